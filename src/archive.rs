@@ -0,0 +1,349 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    sync::mpsc::{sync_channel, SyncSender},
+    thread,
+};
+
+use glob::Pattern;
+
+use crate::{
+    input::{decompress, has_records},
+    reader::LogReader,
+    Log, LogError, LogStream,
+};
+
+/// The bytes a zip archive starts with
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// The bytes a zip archive with no members starts with
+const EMPTY_ZIP_MAGIC: &[u8] = b"PK\x05\x06";
+/// The marker found in the header of a tar archive
+const TAR_MAGIC: &[u8] = b"ustar";
+/// Where abouts in the header of a tar archive the marker is
+const TAR_MAGIC_OFFSET: usize = 257;
+/// How many messages can be read ahead of the ones being printed
+const READ_AHEAD: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Tar,
+    Zip,
+}
+
+fn open_error(path: &str, err: impl std::fmt::Display) -> LogError {
+    LogError {
+        cause: format!("Unable to read archive {}: {}", path, err),
+    }
+}
+
+/// Checks if a file is an archive, tar archives may also be compressed
+fn detect(path: &str) -> Result<Option<ArchiveKind>, LogError> {
+    if !fs::metadata(path).map(|m| m.is_file()).unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let handle = fs::File::open(path).map_err(|err| open_error(path, err))?;
+    let mut reader = BufReader::new(handle);
+    let start = reader.fill_buf().map_err(|err| open_error(path, err))?;
+    if start.starts_with(ZIP_MAGIC) || start.starts_with(EMPTY_ZIP_MAGIC) {
+        return Ok(Some(ArchiveKind::Zip));
+    }
+
+    let mut reader = decompress(reader, path)?;
+    let header = reader.fill_buf().map_err(|err| open_error(path, err))?;
+    if header.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+        return Ok(Some(ArchiveKind::Tar));
+    }
+
+    Ok(None)
+}
+
+pub(crate) fn is_archive(path: &str) -> Result<bool, LogError> {
+    Ok(detect(path)?.is_some())
+}
+
+fn matches(name: &str, members: &[Pattern]) -> bool {
+    members.is_empty() || members.iter().any(|member| member.matches(name))
+}
+
+/// Reads the messages from a member of an archive, returning false if nothing
+/// wants any more messages.
+///
+/// Members that are not logs (e.g. binaries or documentation in a support
+/// bundle) are skipped, with a warning if they were asked for by name.
+fn send_member<R: BufRead>(
+    reader: R,
+    name: &str,
+    selected: bool,
+    sender: &SyncSender<Result<Log, LogError>>,
+) -> Result<bool, LogError> {
+    let mut reader = match decompress(reader, name) {
+        Ok(reader) => reader,
+        Err(err) => {
+            eprintln!("WARNING: Skipping {}: {}", name, err.cause);
+            return Ok(true);
+        }
+    };
+    if !reader.fill_buf().is_ok_and(has_records) {
+        if selected {
+            eprintln!("WARNING: Skipping {} as it is not a log", name);
+        }
+        return Ok(true);
+    }
+
+    for log in LogReader::new(reader, name) {
+        if sender.send(log).is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn read_tar(
+    path: &str,
+    members: &[Pattern],
+    sender: &SyncSender<Result<Log, LogError>>,
+) -> Result<(), LogError> {
+    let handle = fs::File::open(path).map_err(|err| open_error(path, err))?;
+    let mut archive = tar::Archive::new(decompress(BufReader::new(handle), path)?);
+
+    for entry in archive.entries().map_err(|err| open_error(path, err))? {
+        let entry = entry.map_err(|err| open_error(path, err))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let member = entry.path().map_err(|err| open_error(path, err))?;
+        let member = member.to_string_lossy().to_string();
+        if !matches(&member, members) {
+            continue;
+        }
+
+        let name = format!("{}/{}", path, member);
+        if !send_member(BufReader::new(entry), &name, !members.is_empty(), sender)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_zip(
+    path: &str,
+    members: &[Pattern],
+    sender: &SyncSender<Result<Log, LogError>>,
+) -> Result<(), LogError> {
+    let handle = fs::File::open(path).map_err(|err| open_error(path, err))?;
+    let mut archive = zip::ZipArchive::new(handle).map_err(|err| open_error(path, err))?;
+
+    for index in 0..archive.len() {
+        let entry = archive
+            .by_index(index)
+            .map_err(|err| open_error(path, err))?;
+        if !entry.is_file() || !matches(entry.name(), members) {
+            continue;
+        }
+
+        let name = format!("{}/{}", path, entry.name());
+        if !send_member(BufReader::new(entry), &name, !members.is_empty(), sender)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the members of a tar or zip archive as a stream of log messages.
+///
+/// Only the members whose path in the archive matches one of the patterns are
+/// read (or all of them if none are given), each member is read in turn
+/// without needing to extract it and its messages are named after the archive
+/// and the path of the member e.g. `bundle.tar.gz/logs/server.log`.
+pub(crate) fn open(path: &str, members: &[Pattern]) -> Result<LogStream, LogError> {
+    let kind = detect(path)?.ok_or_else(|| LogError {
+        cause: format!("{} is not a tar or zip archive", path),
+    })?;
+
+    let (sender, receiver) = sync_channel(READ_AHEAD);
+    let path = path.to_string();
+    let members = members.to_vec();
+    thread::spawn(move || {
+        let result = match kind {
+            ArchiveKind::Tar => read_tar(&path, &members, &sender),
+            ArchiveKind::Zip => read_zip(&path, &members, &sender),
+        };
+        if let Err(err) = result {
+            let _ = sender.send(Err(err));
+        }
+    });
+
+    Ok(Box::new(receiver.into_iter()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{record, TempDir};
+    use std::io::Write;
+
+    fn read(path: &str, members: &[&str]) -> Vec<(String, String)> {
+        let members: Vec<Pattern> = members.iter().map(|m| Pattern::new(m).unwrap()).collect();
+        open(path, &members)
+            .unwrap()
+            .map(|log| {
+                let log = log.unwrap();
+                (log.source.input, log.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn archive_members() {
+        let dir = TempDir::new("archive");
+
+        let tar_path = dir.join("bundle.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&tar_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (name, text) in [
+            ("logs/client.log", record(0).message("client")),
+            ("logs/server.log", record(0).message("server")),
+            ("readme.txt", String::from("not a log")),
+            ("bin/server", String::from("\u{7f}ELF\u{0}\u{ff}")),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(text.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, text.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let zip_path = dir.join("bundle.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        writer
+            .start_file("server.log", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .write_all(record(0).message("zipped").as_bytes())
+            .unwrap();
+        writer.finish().unwrap();
+
+        let tar_name = tar_path.to_string_lossy().to_string();
+        let zip_name = zip_path.to_string_lossy().to_string();
+        assert!(is_archive(&tar_name).unwrap());
+        assert!(is_archive(&zip_name).unwrap());
+
+        assert_eq!(
+            read(&tar_name, &["*.log"]),
+            vec![
+                (
+                    format!("{}/logs/client.log", tar_name),
+                    String::from("client")
+                ),
+                (
+                    format!("{}/logs/server.log", tar_name),
+                    String::from("server")
+                ),
+            ]
+        );
+        assert_eq!(
+            read(&tar_name, &[]),
+            vec![
+                (
+                    format!("{}/logs/client.log", tar_name),
+                    String::from("client")
+                ),
+                (
+                    format!("{}/logs/server.log", tar_name),
+                    String::from("server")
+                ),
+            ]
+        );
+        assert_eq!(
+            read(&tar_name, &["*/server.log"]),
+            vec![(
+                format!("{}/logs/server.log", tar_name),
+                String::from("server")
+            )]
+        );
+        assert_eq!(
+            read(&zip_name, &[]),
+            vec![(format!("{}/server.log", zip_name), String::from("zipped"))]
+        );
+    }
+
+    /// Writes a zip archive with the given members
+    fn write_zip(path: &std::path::Path, members: &[(&str, &[u8])]) {
+        let mut writer = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, data) in members {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn archive_compressed_members() {
+        let dir = TempDir::new("archive-compressed");
+        let path = dir.join("bundle.zip");
+        write_zip(
+            &path,
+            &[
+                ("server.log.gz", &gzip(&record(0).message("zipped"))),
+                ("notes.txt.gz", &gzip("not a log\n")),
+            ],
+        );
+
+        // a compressed member is only read if it holds a log
+        let name = path.to_string_lossy().to_string();
+        assert_eq!(
+            read(&name, &[]),
+            vec![(format!("{}/server.log.gz", name), String::from("zipped"))]
+        );
+        assert_eq!(read(&name, &["notes.txt.gz"]), vec![]);
+    }
+
+    #[test]
+    fn archive_empty_and_damaged() {
+        let dir = TempDir::new("archive-damaged");
+
+        let empty = dir.join("empty.zip");
+        write_zip(&empty, &[]);
+        assert_eq!(read(&empty.to_string_lossy(), &[]), vec![]);
+
+        // a zip that ends straight after its signature
+        let corrupt = dir.join("corrupt.zip");
+        fs::write(&corrupt, b"PK\x03\x04 not really a zip").unwrap();
+        let corrupt = corrupt.to_string_lossy().to_string();
+        assert!(is_archive(&corrupt).unwrap());
+        assert!(open(&corrupt, &[]).unwrap().any(|log| log.is_err()));
+
+        // a tar cut off part way through a member
+        let truncated = dir.join("truncated.tar");
+        let mut builder = tar::Builder::new(Vec::new());
+        let text = record(0).message("first") + &record(1).message("second");
+        let mut header = tar::Header::new_gnu();
+        header.set_size(text.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "server.log", text.as_bytes())
+            .unwrap();
+        let data = builder.into_inner().unwrap();
+        fs::write(&truncated, &data[..512 + text.len() / 2]).unwrap();
+        let truncated = truncated.to_string_lossy().to_string();
+        assert!(is_archive(&truncated).unwrap());
+        assert!(open(&truncated, &[]).unwrap().any(|log| log.is_err()));
+    }
+}
@@ -0,0 +1,120 @@
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use crate::Log;
+
+/// Matches numbers and hex ids e.g. `42`, `0x7f3a` or `00af12` in a message
+pub(crate) const NUMBER_PATTERN: &str = r"\b(0[xX])?[0-9a-fA-F]*[0-9][0-9a-fA-F]*\b";
+
+/// A run of messages that are the same, represented by the first of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Repeated {
+    pub log: Log,
+    /// how many messages there are in the run
+    pub count: usize,
+    /// the time of the last message in the run
+    pub last: NaiveDateTime,
+}
+
+/// Folds runs of consecutive messages that are the same into one message
+/// along with how many times it was repeated.
+///
+/// Messages are the same if they have the same component, file, line, level
+/// and message, optionally ignoring any numbers and hex ids in the message.
+pub(crate) struct Collapse {
+    numbers: Option<Regex>,
+    current: Option<(String, Repeated)>,
+}
+
+impl Collapse {
+    pub fn new(ignore_numbers: bool) -> Self {
+        Collapse {
+            numbers: ignore_numbers
+                .then(|| Regex::new(NUMBER_PATTERN).expect("Invalid number pattern")),
+            current: None,
+        }
+    }
+
+    fn key(&self, log: &Log) -> String {
+        let message = match &self.numbers {
+            Some(numbers) => numbers.replace_all(&log.message, "#").to_string(),
+            None => log.message.clone(),
+        };
+        format!(
+            "{}\0{}\0{}\0{}\0{}",
+            log.component, log.file, log.line, log.level, message
+        )
+    }
+
+    /// Adds the next message, returning the previous run once a message that
+    /// is different to it is seen
+    pub fn push(&mut self, log: Log) -> Option<Repeated> {
+        let key = self.key(&log);
+        if let Some((current, run)) = &mut self.current {
+            if *current == key {
+                run.count += 1;
+                run.last = run.last.max(log.time);
+                return None;
+            }
+        }
+
+        let run = Repeated {
+            last: log.time,
+            log,
+            count: 1,
+        };
+        self.current.replace((key, run)).map(|(_, run)| run)
+    }
+
+    /// Returns the run being collected once there are no more messages
+    pub fn finish(&mut self) -> Option<Repeated> {
+        self.current.take().map(|(_, run)| run)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    fn collapse(text: &str, ignore_numbers: bool) -> Vec<(String, usize)> {
+        let mut collapse = Collapse::new(ignore_numbers);
+        let mut runs: Vec<Repeated> = logs(text)
+            .into_iter()
+            .filter_map(|log| collapse.push(log))
+            .collect();
+        runs.extend(collapse.finish());
+        runs.into_iter()
+            .map(|run| (run.log.message, run.count))
+            .collect()
+    }
+
+    #[test]
+    fn collapse_runs() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# request 0x1f done
+Pid# 1 Tim# Tue Jul  9 09:09:28 2024 000000us Tid# 2 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# request 0x1f done
+Pid# 1 Tim# Tue Jul  9 09:09:29 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# request 0x2a done
+Pid# 1 Tim# Tue Jul  9 09:09:30 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 2 Lvl# INFO Msg# request 0x2a done
+Pid# 1 Tim# Tue Jul  9 09:09:31 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# request 0x1f done
+";
+
+        assert_eq!(
+            collapse(text, false),
+            vec![
+                (String::from("request 0x1f done"), 2),
+                (String::from("request 0x2a done"), 1),
+                (String::from("request 0x2a done"), 1),
+                (String::from("request 0x1f done"), 1),
+            ]
+        );
+        assert_eq!(
+            collapse(text, true),
+            vec![
+                (String::from("request 0x1f done"), 3),
+                (String::from("request 0x2a done"), 1),
+                (String::from("request 0x1f done"), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use crate::{stats::format_span, Log};
+
+/// Matches the peer of a connection written as `host:port`, the host needs a
+/// letter or a dot so times like `09:09` are not taken for peers
+const PEER_PATTERN: &str =
+    r"(?P<host>\[[0-9a-fA-F:]+\]|[A-Za-z0-9_-]*[A-Za-z.][A-Za-z0-9_.-]*):(?P<port>\d{1,5})\b";
+/// Matches the peer of a connection written as separate host and port pairs
+const HOST_PORT_PATTERN: &str = r"(?i)\bhost\s*[:=]\s*(?P<host>[^\s,;:]+)[\s,;]+(?:\w+\s*[:=]\s*\S+[\s,;]+)*?port\s*[:=]\s*(?P<port>\d{1,5})\b";
+
+/// What a connection manager message says happened to a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Opened,
+    Accepted,
+    Closed,
+    /// the connection was reset or broken, this also ends it
+    Reset,
+    /// something went wrong that did not end an open connection, or a
+    /// connection that could not be opened
+    Failed,
+}
+
+impl Event {
+    /// What happened to the connection a message is about, None if the
+    /// message is not about a connection
+    fn of(message: &str) -> Option<Self> {
+        let message = message.to_lowercase();
+        if !message.contains("connect") && !message.contains("socket") {
+            return None;
+        }
+        let has = |words: &[&str]| words.iter().any(|word| message.contains(word));
+        let event = if has(&["reset", "broken", "abort", "peer closed"]) {
+            Event::Reset
+        } else if has(&["refused", "fail", "timed out", "timeout", "unable", "error"]) {
+            Event::Failed
+        } else if has(&["disconnect", "clos", "shut"]) {
+            Event::Closed
+        } else if has(&["accept"]) {
+            Event::Accepted
+        } else if has(&["open", "establish", "connected", "connecting", "connect to"]) {
+            Event::Opened
+        } else {
+            return None;
+        };
+        Some(event)
+    }
+}
+
+/// A connection from when it was opened or accepted to when it ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Connection {
+    peer: String,
+    incoming: bool,
+    /// None if the messages start after it was opened
+    opened: Option<NaiveDateTime>,
+    /// None if it was never closed
+    closed: Option<NaiveDateTime>,
+    /// the first line of the messages that said something went wrong with it
+    errors: Vec<String>,
+}
+
+impl Connection {
+    fn started(&self) -> Option<NaiveDateTime> {
+        self.opened.or(self.closed)
+    }
+}
+
+/// The connections of a process
+#[derive(Debug, Default)]
+struct Process {
+    connections: Vec<Connection>,
+    /// the indexes of the connections still open to each peer, oldest first
+    open: HashMap<String, VecDeque<usize>>,
+}
+
+/// Tracks each process's connections through the connection manager messages
+/// that open, accept, close or reset them
+pub(crate) struct Connections {
+    peer: Regex,
+    host_port: Regex,
+    processes: BTreeMap<usize, Process>,
+}
+
+impl Connections {
+    pub fn new() -> Self {
+        Connections {
+            peer: Regex::new(PEER_PATTERN).expect("Invalid peer pattern"),
+            host_port: Regex::new(HOST_PORT_PATTERN).expect("Invalid host and port pattern"),
+            processes: BTreeMap::new(),
+        }
+    }
+
+    fn peer(&self, message: &str) -> Option<String> {
+        let captures = self
+            .host_port
+            .captures(message)
+            .or_else(|| self.peer.captures(message))?;
+        Some(format!("{}:{}", &captures["host"], &captures["port"]))
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        let Some(event) = Event::of(&log.message) else {
+            return;
+        };
+        let Some(peer) = self.peer(&log.message) else {
+            return;
+        };
+        let error = log.message.lines().next().unwrap_or_default().to_string();
+
+        let process = self.processes.entry(log.pid).or_default();
+        let open = process.open.entry(peer.clone()).or_default();
+        match event {
+            Event::Opened | Event::Accepted => {
+                open.push_back(process.connections.len());
+                process.connections.push(Connection {
+                    peer,
+                    incoming: event == Event::Accepted,
+                    opened: Some(log.time),
+                    closed: None,
+                    errors: Vec::new(),
+                });
+            }
+            Event::Closed | Event::Reset => {
+                let connection = match open.pop_front() {
+                    Some(index) => &mut process.connections[index],
+                    None => {
+                        // opened before the messages start
+                        process.connections.push(Connection {
+                            peer,
+                            incoming: false,
+                            opened: None,
+                            closed: None,
+                            errors: Vec::new(),
+                        });
+                        process.connections.last_mut().unwrap()
+                    }
+                };
+                connection.closed = Some(log.time);
+                if event == Event::Reset {
+                    connection.errors.push(error);
+                }
+            }
+            Event::Failed => match open.front() {
+                Some(index) => process.connections[*index].errors.push(error),
+                None => process.connections.push(Connection {
+                    peer,
+                    incoming: false,
+                    opened: Some(log.time),
+                    closed: Some(log.time),
+                    errors: vec![error],
+                }),
+            },
+        }
+    }
+
+    /// Prints a table of the connections of each process, or only the ones
+    /// never closed if `open` is set
+    pub fn print(&self, open: bool, date_fmt: &str) {
+        let mut first = true;
+        for (pid, process) in &self.processes {
+            let mut connections: Vec<&Connection> = process
+                .connections
+                .iter()
+                .filter(|connection| !open || connection.closed.is_none())
+                .collect();
+            if connections.is_empty() {
+                continue;
+            }
+            connections.sort_by_key(|connection| connection.started());
+
+            if !first {
+                println!();
+            }
+            first = false;
+
+            let never_closed = process
+                .connections
+                .iter()
+                .filter(|connection| connection.closed.is_none())
+                .count();
+            let with_errors = process
+                .connections
+                .iter()
+                .filter(|connection| !connection.errors.is_empty())
+                .count();
+            println!(
+                "pid {}: {} connections, {} never closed, {} with errors",
+                pid,
+                process.connections.len(),
+                never_closed,
+                with_errors
+            );
+
+            let time = |time: Option<NaiveDateTime>, missing: &str| {
+                time.map(|time| time.format(date_fmt).to_string())
+                    .unwrap_or_else(|| missing.to_string())
+            };
+            for connection in connections {
+                let duration = match (connection.opened, connection.closed) {
+                    (Some(opened), Some(closed)) => format_span(closed - opened),
+                    _ => String::from("-"),
+                };
+                println!(
+                    "  {} {}  opened {}  closed {}  {}",
+                    if connection.incoming { "from" } else { "to" },
+                    connection.peer,
+                    time(connection.opened, "before the start"),
+                    time(connection.closed, "never"),
+                    duration
+                );
+                for error in &connection.errors {
+                    println!("    error: {}", error);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    #[test]
+    fn track_connections() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:00 2024 000000us Tid# 1 Log# connection Src# client Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# Connection opened to bank.example.com:14000 at 09:09:00
+Pid# 1 Tim# Tue Jul  9 09:09:01 2024 000000us Tid# 1 Log# connection Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# Accepted connection from 10.0.0.5:51234
+Pid# 1 Tim# Tue Jul  9 09:09:03 2024 000000us Tid# 1 Log# connection Src# client Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# Connection to bank.example.com:14000 closed
+Pid# 1 Tim# Tue Jul  9 09:09:04 2024 000000us Tid# 1 Log# connection Src# client Fil# vorb.C Lin# 1 Lvl# WARN Msg# connect to host=db.example.com, port=15000 refused
+Pid# 1 Tim# Tue Jul  9 09:09:05 2024 000000us Tid# 1 Log# connection Src# server Fil# vorb.C Lin# 1 Lvl# WARN Msg# connection from 10.0.0.5:51234 reset by peer
+Pid# 2 Tim# Tue Jul  9 09:09:06 2024 000000us Tid# 1 Log# connection Src# client Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# Connection established to bank.example.com:14000
+Pid# 2 Tim# Tue Jul  9 09:09:07 2024 000000us Tid# 1 Log# connection Src# client Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# nothing to do with it at 09:09:07
+";
+        let mut connections = Connections::new();
+        for log in logs(text) {
+            connections.add(&log);
+        }
+
+        let summary: Vec<(usize, &str, bool, Option<i64>, usize)> = connections
+            .processes
+            .iter()
+            .flat_map(|(pid, process)| {
+                process.connections.iter().map(move |connection| {
+                    (
+                        *pid,
+                        connection.peer.as_str(),
+                        connection.incoming,
+                        connection
+                            .opened
+                            .zip(connection.closed)
+                            .map(|(opened, closed)| (closed - opened).num_seconds()),
+                        connection.errors.len(),
+                    )
+                })
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, "bank.example.com:14000", false, Some(3), 0),
+                (1, "10.0.0.5:51234", true, Some(4), 1),
+                (1, "db.example.com:15000", false, Some(0), 1),
+                (2, "bank.example.com:14000", false, None, 0),
+            ]
+        );
+        assert!(connections.processes[&2].connections[0].closed.is_none());
+    }
+}
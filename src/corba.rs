@@ -0,0 +1,518 @@
+use std::{collections::HashMap, fs};
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{Log, LogError};
+
+/// The names of the CORBA system exceptions
+const SYSTEM_EXCEPTIONS: [&str; 37] = [
+    "UNKNOWN",
+    "BAD_PARAM",
+    "NO_MEMORY",
+    "IMP_LIMIT",
+    "COMM_FAILURE",
+    "INV_OBJREF",
+    "NO_PERMISSION",
+    "INTERNAL",
+    "MARSHAL",
+    "INITIALIZE",
+    "NO_IMPLEMENT",
+    "BAD_TYPECODE",
+    "BAD_OPERATION",
+    "NO_RESOURCES",
+    "NO_RESPONSE",
+    "PERSIST_STORE",
+    "BAD_INV_ORDER",
+    "TRANSIENT",
+    "FREE_MEM",
+    "INV_IDENT",
+    "INV_FLAG",
+    "INTF_REPOS",
+    "BAD_CONTEXT",
+    "OBJ_ADAPTER",
+    "DATA_CONVERSION",
+    "OBJECT_NOT_EXIST",
+    "TRANSACTION_REQUIRED",
+    "TRANSACTION_ROLLEDBACK",
+    "INVALID_TRANSACTION",
+    "INV_POLICY",
+    "CODESET_INCOMPATIBLE",
+    "REBIND",
+    "TIMEOUT",
+    "TRANSACTION_UNAVAILABLE",
+    "TRANSACTION_MODE",
+    "BAD_QOS",
+    "INVALID_ACTIVITY",
+];
+
+/// The vendor minor code set id of the minor codes defined by the OMG
+pub(crate) const OMG_VMCID: u32 = 0x4f4d_0000;
+/// The vendor minor code set id of VisiBroker's own minor codes
+pub(crate) const VISIBROKER_VMCID: u32 = 0x5642_0000;
+
+/// The minor codes defined by the CORBA specification, the vendor minor code
+/// set id (OMG_VMCID) is left off
+const OMG_MINOR_CODES: [(&str, u32, &str); 38] = [
+    ("BAD_INV_ORDER", 1, "dependency exists in the interface repository preventing destruction of this object"),
+    ("BAD_INV_ORDER", 2, "attempt to destroy indestructible objects in the interface repository"),
+    ("BAD_INV_ORDER", 3, "operation would deadlock"),
+    ("BAD_INV_ORDER", 4, "ORB has shutdown"),
+    ("BAD_INV_ORDER", 5, "attempt to invoke send or invoke on the same request more than once"),
+    ("BAD_INV_ORDER", 6, "attempt to set a servant manager after one has already been set"),
+    ("BAD_OPERATION", 1, "servant manager returned the wrong servant type"),
+    ("BAD_OPERATION", 2, "operation or attribute not known to the target object"),
+    ("BAD_PARAM", 1, "failure to register, unregister or look up a value factory"),
+    ("BAD_PARAM", 2, "repository id already defined in the interface repository"),
+    ("BAD_PARAM", 3, "name already used in the context in the interface repository"),
+    ("BAD_PARAM", 4, "target is not a valid container"),
+    ("BAD_PARAM", 5, "name clash in an inherited context"),
+    ("BAD_PARAM", 6, "incorrect type for an abstract interface"),
+    ("BAD_PARAM", 7, "string contains a null character"),
+    ("MARSHAL", 1, "unable to locate a value factory"),
+    ("MARSHAL", 2, "ServerRequest::set_result called before ServerRequest::ctx"),
+    ("MARSHAL", 3, "argument list does not describe all of the parameters passed by the client"),
+    ("MARSHAL", 4, "attempt to marshal a local object"),
+    ("NO_IMPLEMENT", 1, "missing local value implementation"),
+    ("NO_IMPLEMENT", 2, "incompatible value implementation version"),
+    ("NO_IMPLEMENT", 3, "unable to use any profile in the IOR"),
+    ("NO_IMPLEMENT", 4, "attempt to use DII on a local object"),
+    ("OBJ_ADAPTER", 1, "system exception in AdapterActivator::unknown_adapter"),
+    ("OBJ_ADAPTER", 2, "incorrect servant type returned by the servant manager"),
+    ("OBJ_ADAPTER", 3, "no default servant available"),
+    ("OBJ_ADAPTER", 4, "no servant manager available"),
+    ("OBJ_ADAPTER", 5, "violation of POA policy by ServantActivator::incarnate"),
+    ("OBJECT_NOT_EXIST", 1, "attempt to pass an unactivated value as an object reference"),
+    ("OBJECT_NOT_EXIST", 2, "failed to create or locate the object adapter"),
+    ("OBJECT_NOT_EXIST", 3, "biomolecular sequence analysis service is no longer available"),
+    ("OBJECT_NOT_EXIST", 4, "object adapter inactive"),
+    ("OBJECT_NOT_EXIST", 5, "poller has already delivered a reply to some client"),
+    ("TRANSIENT", 1, "request discarded because of resource exhaustion in the POA or because the POA is discarding requests"),
+    ("TRANSIENT", 2, "no usable profile in the IOR"),
+    ("TRANSIENT", 3, "request cancelled"),
+    ("TRANSIENT", 4, "POA destroyed"),
+    ("UNKNOWN", 1, "unlisted user exception received by the client"),
+];
+
+/// VisiBroker's own minor codes, the vendor minor code set id (VISIBROKER_VMCID)
+/// is left off. The codes can change between VisiBroker versions so any given
+/// with --minor-codes are used instead
+const VISIBROKER_MINOR_CODES: [(&str, u32, &str); 12] = [
+    ("BAD_PARAM", 1, "invalid stringified object reference"),
+    ("COMM_FAILURE", 1, "unable to connect to the server"),
+    (
+        "COMM_FAILURE",
+        2,
+        "connection closed while waiting for a reply",
+    ),
+    (
+        "COMM_FAILURE",
+        3,
+        "error writing the request to the connection",
+    ),
+    (
+        "COMM_FAILURE",
+        4,
+        "error reading the reply from the connection",
+    ),
+    ("INV_OBJREF", 1, "object reference has no usable profile"),
+    (
+        "NO_PERMISSION",
+        1,
+        "request rejected by the security policy of the server",
+    ),
+    (
+        "NO_RESOURCES",
+        1,
+        "no more connections or threads available",
+    ),
+    ("OBJECT_NOT_EXIST", 1, "object key not known to the server"),
+    ("TIMEOUT", 1, "no reply before the request timed out"),
+    (
+        "TRANSIENT",
+        1,
+        "server not reachable, the request can be retried",
+    ),
+    (
+        "TRANSIENT",
+        2,
+        "connection closed by the server before the reply",
+    ),
+];
+
+/// A CORBA system exception found in a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SystemException {
+    pub name: String,
+    /// the full minor code including the vendor minor code set id
+    pub minor: Option<u32>,
+    /// YES, NO or MAYBE
+    pub completed: Option<String>,
+}
+
+/// Formats a minor code in the way it is usually written e.g. `0x4f4d0002`
+pub(crate) fn format_minor(minor: u32) -> String {
+    format!("{:#010x}", minor)
+}
+
+/// A minor code as written in a minor codes file
+#[derive(Debug, Deserialize)]
+struct MinorCode {
+    /// the exception the code is for, if not given the code applies to all of them
+    exception: Option<String>,
+    code: u32,
+    reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MinorCodesFile {
+    #[serde(default)]
+    minor: Vec<MinorCode>,
+}
+
+/// Finds CORBA system exceptions in messages and looks up what their minor
+/// codes mean
+#[derive(Debug)]
+pub(crate) struct Exceptions {
+    /// an exception with one of the prefixes CORBA exceptions are written with
+    exception: Regex,
+    /// an exception written on its own, only taken to be one if it is followed
+    /// by a minor code or completion status on the same line
+    bare_exception: Regex,
+    minor: Regex,
+    vmcid: Regex,
+    completed: Regex,
+    /// reasons keyed by exception (empty for any) and full minor code
+    reasons: HashMap<(String, u32), String>,
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+impl Exceptions {
+    pub fn new() -> Self {
+        let exception = format!(
+            r"(?:\bCORBA::|\borg\.omg\.CORBA\.|\bIDL:omg\.org/CORBA/)(?P<name>{})(?::1\.0)?\b",
+            SYSTEM_EXCEPTIONS.join("|")
+        );
+        let bare_exception = format!(r"\b(?P<name>{})\b", SYSTEM_EXCEPTIONS.join("|"));
+        let omg = OMG_MINOR_CODES.iter().map(|code| (OMG_VMCID, code));
+        let visibroker = VISIBROKER_MINOR_CODES
+            .iter()
+            .map(|code| (VISIBROKER_VMCID, code));
+        let reasons = omg
+            .chain(visibroker)
+            .map(|(vmcid, (name, code, reason))| {
+                ((name.to_string(), vmcid | code), reason.to_string())
+            })
+            .collect();
+
+        Exceptions {
+            exception: Regex::new(&exception).expect("Invalid exception pattern"),
+            bare_exception: Regex::new(&bare_exception).expect("Invalid exception pattern"),
+            minor: Regex::new(r"(?i)\bminor(?:\s*code)?\s*[:=]?\s*(?P<minor>0x[0-9a-f]+|\d+)")
+                .expect("Invalid minor code pattern"),
+            vmcid: Regex::new(r"(?i)\bvmcid\s*[:=]?\s*(?P<vmcid>0x[0-9a-f]+|\d+|OMG)")
+                .expect("Invalid vmcid pattern"),
+            completed: Regex::new(
+                r"(?i)\bcompleted?(?:\s*[:=]\s*|\s+)(?:COMPLETED_)?(?P<completed>YES|NO|MAYBE)\b",
+            )
+            .expect("Invalid completion status pattern"),
+            reasons,
+        }
+    }
+
+    /// Adds the minor codes in a toml file to the ones that can be looked up,
+    /// replacing any built in ones with the same code. Each is a `[[minor]]`
+    /// table with a `code`, a `reason` and optionally the `exception` it is for
+    pub fn with_minor_codes(mut self, path: &str) -> Result<Self, LogError> {
+        let text = fs::read_to_string(path).map_err(|err| LogError {
+            cause: format!("Unable to read minor codes {}: {}", path, err),
+        })?;
+        let file: MinorCodesFile = toml::from_str(&text).map_err(|err| LogError {
+            cause: format!("Unable to parse minor codes {}: {}", path, err),
+        })?;
+        for minor in file.minor {
+            let exception = minor.exception.unwrap_or_default().to_uppercase();
+            if exception.is_empty() {
+                // a code given for any exception replaces the built in ones for each
+                self.reasons.retain(|(_, code), _| *code != minor.code);
+            }
+            self.reasons.insert((exception, minor.code), minor.reason);
+        }
+        Ok(self)
+    }
+
+    /// Finds where the name of a system exception is in a message, a name
+    /// without a prefix must be followed by a minor code or completion status
+    /// on the same line so words such as UNKNOWN or TRANSIENT are not mistaken
+    /// for one
+    fn find_name<'a>(&self, message: &'a str) -> Option<regex::Captures<'a>> {
+        if let Some(captures) = self.exception.captures(message) {
+            return Some(captures);
+        }
+
+        self.bare_exception.captures_iter(message).find(|captures| {
+            let end = captures.get(0).map_or(0, |name| name.end());
+            let line = message[end..].lines().next().unwrap_or_default();
+            self.minor.is_match(line) || self.completed.is_match(line)
+        })
+    }
+
+    /// Finds the first system exception in a message along with its minor
+    /// code and completion status if they are given after it
+    pub fn find(&self, message: &str) -> Option<SystemException> {
+        let captures = self.find_name(message)?;
+        let rest = &message[captures.get(0)?.end()..];
+
+        let minor = self
+            .minor
+            .captures(rest)
+            .and_then(|minor| parse_number(&minor["minor"]))
+            .map(|minor| {
+                // java prints the vendor minor code set id apart from the minor code
+                let vmcid = self.vmcid.captures(rest).and_then(|vmcid| {
+                    match vmcid["vmcid"].to_uppercase().as_str() {
+                        "OMG" => Some(OMG_VMCID),
+                        vmcid => parse_number(&vmcid.to_lowercase()),
+                    }
+                });
+                match vmcid {
+                    Some(vmcid) if minor & 0xffff_f000 == 0 => vmcid | minor,
+                    _ => minor,
+                }
+            });
+        let completed = self
+            .completed
+            .captures(rest)
+            .map(|completed| completed["completed"].to_uppercase());
+
+        Some(SystemException {
+            name: captures["name"].to_string(),
+            minor,
+            completed,
+        })
+    }
+
+    /// Describes what a minor code means, or at least who defined it
+    pub fn reason(&self, exception: &str, minor: u32) -> String {
+        let known = self
+            .reasons
+            .get(&(exception.to_string(), minor))
+            .or_else(|| self.reasons.get(&(String::new(), minor)));
+        if let Some(reason) = known {
+            return reason.clone();
+        }
+
+        match minor & 0xffff_f000 {
+            OMG_VMCID => format!("OMG standard minor code {}", minor & 0xfff),
+            VISIBROKER_VMCID => format!("VisiBroker minor code {}", minor & 0xfff),
+            0 => String::from("minor code without a vendor id"),
+            vmcid => format!("minor code {} of vendor {:#010x}", minor & 0xfff, vmcid),
+        }
+    }
+
+    /// Adds the `exception`, `minor`, `minor_reason` and `completed` fields to
+    /// a message, they are empty if the message has no system exception in it
+    pub fn extract(&self, log: &mut Log) {
+        let found = self.find(&log.message);
+        let (exception, minor, reason, completed) = match &found {
+            Some(found) => (
+                found.name.clone(),
+                found.minor.map(format_minor).unwrap_or_default(),
+                found
+                    .minor
+                    .map(|minor| self.reason(&found.name, minor))
+                    .unwrap_or_default(),
+                found.completed.clone().unwrap_or_default(),
+            ),
+            None => Default::default(),
+        };
+        log.fields.insert(String::from("exception"), exception);
+        log.fields.insert(String::from("minor"), minor);
+        log.fields.insert(String::from("minor_reason"), reason);
+        log.fields.insert(String::from("completed"), completed);
+    }
+}
+
+/// The names of the fields added by `Exceptions::extract`
+pub(crate) const EXCEPTION_FIELDS: [&str; 4] = ["completed", "exception", "minor", "minor_reason"];
+
+/// When an exception was seen and by which processes
+struct Seen {
+    count: usize,
+    first: NaiveDateTime,
+    last: NaiveDateTime,
+    reason: String,
+    pids: Vec<usize>,
+}
+
+/// How many times each exception, minor code and completion status were seen
+#[derive(Default)]
+pub(crate) struct ExceptionSummary {
+    /// keyed by the exception, minor code and completion status
+    seen: HashMap<(String, String, String), Seen>,
+}
+
+impl ExceptionSummary {
+    /// Adds a message that has had its exception fields extracted
+    pub fn add(&mut self, log: &Log) {
+        let field = |name: &str| log.fields.get(name).cloned().unwrap_or_default();
+        let exception = field("exception");
+        if exception.is_empty() {
+            return;
+        }
+
+        let seen = self
+            .seen
+            .entry((exception, field("minor"), field("completed")))
+            .or_insert_with(|| Seen {
+                count: 0,
+                first: log.time,
+                last: log.time,
+                reason: field("minor_reason"),
+                pids: Vec::new(),
+            });
+        seen.count += 1;
+        seen.first = seen.first.min(log.time);
+        seen.last = seen.last.max(log.time);
+        if !seen.pids.contains(&log.pid) {
+            seen.pids.push(log.pid);
+        }
+    }
+
+    /// Prints each exception with how many times it was seen, the most common first
+    pub fn print(&self, date_fmt: &str) {
+        let mut rows: Vec<_> = self.seen.iter().collect();
+        rows.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+
+        for ((exception, minor, completed), seen) in rows {
+            let mut title = exception.clone();
+            if !minor.is_empty() {
+                title.push_str(&format!(" minor {}", minor));
+            }
+            if !completed.is_empty() {
+                title.push_str(&format!(" completed {}", completed));
+            }
+            println!("{:>7}  {}", seen.count, title);
+            if !seen.reason.is_empty() {
+                println!("         {}", seen.reason);
+            }
+            let pids: Vec<String> = seen.pids.iter().map(usize::to_string).collect();
+            println!(
+                "         {} - {} in pid {}",
+                seen.first.format(date_fmt),
+                seen.last.format(date_fmt),
+                pids.join(", ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn find_exceptions() {
+        let exceptions = Exceptions::new();
+        let cases = vec![
+            (
+                "invoke failed: CORBA::TRANSIENT minor=0x4f4d0002 completed=NO",
+                Some(("TRANSIENT", Some(0x4f4d0002), Some("NO"))),
+            ),
+            (
+                "org.omg.CORBA.OBJECT_NOT_EXIST:   vmcid: OMG  minor code: 2  completed: No",
+                Some(("OBJECT_NOT_EXIST", Some(0x4f4d0002), Some("NO"))),
+            ),
+            (
+                "org.omg.CORBA.COMM_FAILURE: vmcid: 0x56420000  minor code: 17 completed: Maybe",
+                Some(("COMM_FAILURE", Some(0x56420011), Some("MAYBE"))),
+            ),
+            (
+                "caught IDL:omg.org/CORBA/NO_PERMISSION:1.0, completed COMPLETED_YES",
+                Some(("NO_PERMISSION", None, Some("YES"))),
+            ),
+            (
+                "TRANSIENT minor=0x4f4d0002 completed=NO",
+                Some(("TRANSIENT", Some(0x4f4d0002), Some("NO"))),
+            ),
+            (
+                "retrying\nCOMM_FAILURE completed: MAYBE",
+                Some(("COMM_FAILURE", None, Some("MAYBE"))),
+            ),
+            ("transient error talking to TRANSIENT_STORE", None),
+            ("nothing to see here", None),
+            // a name on its own isn't enough
+            ("job state is UNKNOWN", None),
+            ("TRANSIENT failure in the queue, retrying", None),
+            ("INTERNAL error\nminor=2 completed=NO", None),
+            ("state UNKNOWN after TIMEOUT", None),
+        ];
+
+        for (message, expected) in cases {
+            let found = exceptions.find(message);
+            let found = found
+                .as_ref()
+                .map(|found| (found.name.as_str(), found.minor, found.completed.as_deref()));
+            assert_eq!(found, expected, "Checking {}", message);
+        }
+
+        assert_eq!(
+            exceptions.reason("TRANSIENT", 0x4f4d0002),
+            "no usable profile in the IOR"
+        );
+        assert_eq!(
+            exceptions.reason("COMM_FAILURE", 0x56420001),
+            "unable to connect to the server"
+        );
+        assert_eq!(
+            exceptions.reason("COMM_FAILURE", 0x56420011),
+            "VisiBroker minor code 17"
+        );
+        assert_eq!(format_minor(0x4f4d0002), "0x4f4d0002");
+    }
+
+    #[test]
+    fn minor_codes_override() {
+        let dir = TempDir::new("minor-codes");
+        let path = dir.join("minor.toml");
+        fs::write(
+            &path,
+            "\
+[[minor]]
+exception = \"COMM_FAILURE\"
+code = 0x56420001
+reason = \"listener not started\"
+
+[[minor]]
+code = 0x56420002
+reason = \"peer went away\"
+",
+        )
+        .unwrap();
+
+        let exceptions = Exceptions::new()
+            .with_minor_codes(&path.to_string_lossy())
+            .unwrap();
+        assert_eq!(
+            exceptions.reason("COMM_FAILURE", 0x56420001),
+            "listener not started"
+        );
+        assert_eq!(
+            exceptions.reason("COMM_FAILURE", 0x56420002),
+            "peer went away"
+        );
+        assert_eq!(exceptions.reason("TRANSIENT", 0x56420002), "peer went away");
+        assert_eq!(
+            exceptions.reason("TRANSIENT", 0x56420001),
+            "server not reachable, the request can be retried"
+        );
+    }
+}
@@ -0,0 +1,457 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt, fs,
+    path::Path,
+};
+
+use chrono::{NaiveDateTime, TimeDelta};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{stats::parse_duration, Log, LogError};
+
+/// How bad a known problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "INFO",
+            Severity::Low => "LOW",
+            Severity::Medium => "MEDIUM",
+            Severity::High => "HIGH",
+            Severity::Critical => "CRITICAL",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A signature as written in the signatures file
+#[derive(Debug, Deserialize)]
+struct SignatureDefinition {
+    name: String,
+    severity: Severity,
+    #[serde(default)]
+    description: String,
+    remediation: String,
+    /// a regex for each field that has to match
+    #[serde(rename = "match")]
+    patterns: BTreeMap<String, String>,
+    /// a regex for each field of a record that has to have been seen earlier
+    /// in the same process
+    #[serde(default)]
+    after: BTreeMap<String, String>,
+    /// how many matching records a process needs before it is a finding
+    #[serde(default)]
+    count: Option<usize>,
+    /// the time the count has to happen within e.g. 30s, 5m
+    #[serde(default)]
+    within: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SignaturesFile {
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    signature: Vec<SignatureDefinition>,
+}
+
+/// Regexes over the fields of a record that all have to match
+#[derive(Debug)]
+struct Patterns(Vec<(String, Regex)>);
+
+impl Patterns {
+    fn new(path: &str, name: &str, patterns: BTreeMap<String, String>) -> Result<Self, LogError> {
+        patterns
+            .into_iter()
+            .map(|(field, pattern)| {
+                let regex = Regex::new(&pattern).map_err(|err| LogError {
+                    cause: format!(
+                        "Invalid pattern for {} in signature {} in {}: {}",
+                        field, name, path, err
+                    ),
+                })?;
+                Ok((field, regex))
+            })
+            .collect::<Result<_, _>>()
+            .map(Patterns)
+    }
+
+    /// Fields a record does not have never match
+    fn matches(&self, log: &Log) -> bool {
+        self.0
+            .iter()
+            .all(|(field, regex)| value(log, field).is_some_and(|value| regex.is_match(&value)))
+    }
+}
+
+/// The value of a field of a record, including the extra fields extracted
+/// from its message
+fn value(log: &Log, field: &str) -> Option<String> {
+    let value = match field {
+        "pid" => log.pid.to_string(),
+        "tid" => log.tid.to_string(),
+        "logger" => log.logger.clone(),
+        "component" => log.component.clone(),
+        "file" => log.file.clone(),
+        "line" => log.line.to_string(),
+        "level" => log.level.to_string(),
+        "message" => log.message.clone(),
+        "input" => log.source.input.clone(),
+        field => return log.fields.get(field).cloned(),
+    };
+    Some(value)
+}
+
+/// A known problem and how to recognise it
+#[derive(Debug)]
+struct Signature {
+    name: String,
+    severity: Severity,
+    description: String,
+    remediation: String,
+    patterns: Patterns,
+    after: Option<Patterns>,
+    count: usize,
+    within: Option<TimeDelta>,
+}
+
+impl Signature {
+    fn new(path: &str, definition: SignatureDefinition) -> Result<Self, LogError> {
+        let name = definition.name;
+        if definition.patterns.is_empty() {
+            return Err(LogError {
+                cause: format!("Signature {} in {} has nothing to match", name, path),
+            });
+        }
+        let patterns = Patterns::new(path, &name, definition.patterns)?;
+        let after = if definition.after.is_empty() {
+            None
+        } else {
+            Some(Patterns::new(path, &name, definition.after)?)
+        };
+        let within = definition
+            .within
+            .map(|within| {
+                parse_duration(&within).map_err(|err| LogError {
+                    cause: format!("Invalid within in signature {} in {}: {}", name, path, err),
+                })
+            })
+            .transpose()?;
+
+        Ok(Signature {
+            name,
+            severity: definition.severity,
+            description: definition.description,
+            remediation: definition.remediation,
+            patterns,
+            after,
+            count: definition.count.unwrap_or(1).max(1),
+            within,
+        })
+    }
+}
+
+/// Reads the signatures from a toml file, each is a `[[signature]]` table with
+/// a `name`, `severity`, `remediation` and a `match` table of field regexes,
+/// optionally an `after` table, a `count` and a `within` duration
+fn load(path: &str) -> Result<(Option<String>, Vec<Signature>), LogError> {
+    let text = fs::read_to_string(path).map_err(|err| LogError {
+        cause: format!("Unable to read signatures {}: {}", path, err),
+    })?;
+    // the file is toml unless it is named as yaml
+    let yaml = Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension == "yaml" || extension == "yml");
+    let file: SignaturesFile = if yaml {
+        serde_yaml::from_str(&text).map_err(|err| err.to_string())
+    } else {
+        toml::from_str(&text).map_err(|err| err.to_string())
+    }
+    .map_err(|err| LogError {
+        cause: format!("Unable to parse signatures {}: {}", path, err),
+    })?;
+    if file.signature.is_empty() {
+        return Err(LogError {
+            cause: format!("No signatures found in {}", path),
+        });
+    }
+
+    let signatures = file
+        .signature
+        .into_iter()
+        .map(|definition| Signature::new(path, definition))
+        .collect::<Result<_, _>>()?;
+    Ok((file.version, signatures))
+}
+
+/// What has been seen of a signature
+#[derive(Debug, Default)]
+struct Matches {
+    /// how many records matched in each process, whether or not the count
+    /// was reached
+    records: HashMap<usize, usize>,
+    /// the processes that have seen the `after` record
+    primed: BTreeSet<usize>,
+    /// the times of the recent matches in each process
+    recent: HashMap<usize, VecDeque<NaiveDateTime>>,
+    /// the processes the count was reached in
+    pids: BTreeSet<usize>,
+    first: Option<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+    /// the record that first made it a finding
+    example: Option<Log>,
+}
+
+impl Matches {
+    /// How many records matched in the processes the count was reached in
+    fn found_records(&self) -> usize {
+        self.pids
+            .iter()
+            .map(|pid| self.records.get(pid).copied().unwrap_or_default())
+            .sum()
+    }
+}
+
+/// Matches records against a file of signatures of known problems and
+/// reports the ones found with what to do about them
+pub(crate) struct Diagnose {
+    path: String,
+    version: Option<String>,
+    signatures: Vec<Signature>,
+    matches: Vec<Matches>,
+}
+
+impl Diagnose {
+    pub fn new(path: &str) -> Result<Self, LogError> {
+        let (version, signatures) = load(path)?;
+        let matches = signatures.iter().map(|_| Matches::default()).collect();
+        Ok(Diagnose {
+            path: path.to_string(),
+            version,
+            signatures,
+            matches,
+        })
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        for (signature, matches) in self.signatures.iter().zip(&mut self.matches) {
+            // a record can prime its process as well as match, it only counts
+            // if the process was primed by an earlier record
+            let primed = matches.primed.contains(&log.pid);
+            if signature
+                .after
+                .as_ref()
+                .is_some_and(|after| after.matches(log))
+            {
+                matches.primed.insert(log.pid);
+            }
+            if !signature.patterns.matches(log) || (signature.after.is_some() && !primed) {
+                continue;
+            }
+            *matches.records.entry(log.pid).or_default() += 1;
+
+            let recent = matches.recent.entry(log.pid).or_default();
+            recent.push_back(log.time);
+            if let Some(within) = signature.within {
+                while recent
+                    .front()
+                    .is_some_and(|first| log.time - *first > within)
+                {
+                    recent.pop_front();
+                }
+            }
+            if recent.len() < signature.count {
+                continue;
+            }
+            // only the last count are needed to know if it is reached again
+            while recent.len() > signature.count {
+                recent.pop_front();
+            }
+
+            matches.pids.insert(log.pid);
+            matches.first = Some(matches.first.map_or(log.time, |first| first.min(log.time)));
+            matches.last = matches.last.max(Some(log.time));
+            if matches.example.is_none() {
+                matches.example = Some(log.clone());
+            }
+        }
+    }
+
+    /// Prints the signatures found, the most severe first
+    pub fn print(&self, date_fmt: &str) {
+        let mut findings: Vec<(&Signature, &Matches)> = self
+            .signatures
+            .iter()
+            .zip(&self.matches)
+            .filter(|(_, matches)| matches.example.is_some())
+            .collect();
+        findings.sort_by(|a, b| {
+            b.0.severity
+                .cmp(&a.0.severity)
+                .then(a.1.first.cmp(&b.1.first))
+        });
+
+        let version = self
+            .version
+            .as_ref()
+            .map(|version| format!(" version {}", version))
+            .unwrap_or_default();
+        println!(
+            "{}{}: {} of {} signatures found",
+            self.path,
+            version,
+            findings.len(),
+            self.signatures.len()
+        );
+
+        for (signature, matches) in findings {
+            println!();
+            let pids: Vec<String> = matches.pids.iter().map(usize::to_string).collect();
+            println!(
+                "[{}] {}: {} records in pid {}",
+                signature.severity,
+                signature.name,
+                matches.found_records(),
+                pids.join(", ")
+            );
+            if !signature.description.is_empty() {
+                println!("  {}", signature.description);
+            }
+            if let (Some(first), Some(last)) = (matches.first, matches.last) {
+                println!("  {} - {}", first.format(date_fmt), last.format(date_fmt));
+            }
+            if let Some(example) = &matches.example {
+                println!(
+                    "  e.g. {}: {}",
+                    example.source,
+                    example.message.lines().next().unwrap_or_default()
+                );
+            }
+            println!("  remediation: {}", signature.remediation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{logs, TempDir};
+
+    #[test]
+    fn find_signatures() {
+        let dir = TempDir::new("diagnose");
+        let signatures = dir.join("signatures.toml");
+        fs::write(
+            &signatures,
+            r#"
+version = "2024.07"
+
+[[signature]]
+name = "transient"
+severity = "high"
+remediation = "check the server is running"
+match = { level = "^ERROR$", message = 'TRANSIENT' }
+
+[[signature]]
+name = "retry-storm"
+severity = "medium"
+remediation = "look at the retry settings"
+match = { message = '^retrying' }
+count = 3
+within = "10s"
+
+[[signature]]
+name = "lost-after-shutdown"
+severity = "critical"
+remediation = "stop calling after shutdown"
+match = { message = 'OBJECT_NOT_EXIST' }
+after = { message = 'ORB shutdown' }
+
+[[signature]]
+name = "never"
+severity = "info"
+remediation = "nothing"
+match = { component = '^nowhere$' }
+"#,
+        )
+        .unwrap();
+
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:00 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# ERROR Msg# CORBA::TRANSIENT minor=0x4f4d0002
+Pid# 1 Tim# Tue Jul  9 09:09:01 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# retrying ping
+Pid# 1 Tim# Tue Jul  9 09:09:02 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# CORBA::OBJECT_NOT_EXIST
+Pid# 1 Tim# Tue Jul  9 09:09:20 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# retrying ping
+Pid# 1 Tim# Tue Jul  9 09:09:21 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# retrying ping
+Pid# 2 Tim# Tue Jul  9 09:09:22 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# retrying ping
+Pid# 1 Tim# Tue Jul  9 09:09:23 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# retrying ping
+Pid# 1 Tim# Tue Jul  9 09:09:24 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# ORB shutdown
+Pid# 1 Tim# Tue Jul  9 09:09:25 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# WARNING Msg# CORBA::OBJECT_NOT_EXIST
+";
+        let mut diagnose = Diagnose::new(&signatures.to_string_lossy()).unwrap();
+        for log in logs(text) {
+            diagnose.add(&log);
+        }
+        assert_eq!(diagnose.version.as_deref(), Some("2024.07"));
+
+        let found: Vec<(&str, usize, Option<usize>)> = diagnose
+            .signatures
+            .iter()
+            .zip(&diagnose.matches)
+            .map(|(signature, matches)| {
+                (
+                    signature.name.as_str(),
+                    matches.found_records(),
+                    matches.example.as_ref().map(|log| log.source.line),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("transient", 1, Some(1)),
+                // the retry in pid 2 isn't counted as it only happened once there
+                ("retry-storm", 4, Some(7)),
+                ("lost-after-shutdown", 1, Some(9)),
+                ("never", 0, None),
+            ]
+        );
+
+        fs::write(&signatures, "[[signature]]\nname = \"bad\"\nseverity = \"high\"\nremediation = \"\"\nmatch = { message = '(' }\n").unwrap();
+        assert!(Diagnose::new(&signatures.to_string_lossy()).is_err());
+
+        // a record can prime its process for the records after it
+        let signatures = dir.join("signatures.yaml");
+        fs::write(
+            &signatures,
+            r#"
+signature:
+  - name: shutdown-twice
+    severity: low
+    remediation: only shut down once
+    match: { message: 'ORB shutdown' }
+    after: { message: 'ORB shutdown' }
+"#,
+        )
+        .unwrap();
+        let mut diagnose = Diagnose::new(&signatures.to_string_lossy()).unwrap();
+        for log in logs(text) {
+            diagnose.add(&log);
+        }
+        assert_eq!(diagnose.matches[0].found_records(), 0);
+        let again = text.lines().nth(7).unwrap().to_string() + "\n";
+        for log in logs(&again) {
+            diagnose.add(&log);
+        }
+        assert_eq!(diagnose.matches[0].found_records(), 1);
+    }
+}
@@ -0,0 +1,262 @@
+use regex::Regex;
+
+use crate::{
+    corba::{Exceptions, EXCEPTION_FIELDS},
+    Log, LogError,
+};
+
+/// Matches `key=value` and `key: value` pairs, the value can be quoted. A space
+/// is needed after a colon so times and IORs are not mistaken for pairs
+const KV_PATTERN: &str =
+    r#"\b(?P<key>[A-Za-z_][\w.-]*)(?:\s*=\s*|:\s+)(?P<value>"(?:[^"\\]|\\.)*"|'[^']*'|[^\s,;"']+)"#;
+/// What the names of the fields found by key=value pairs start with
+pub(crate) const KV_PREFIX: &str = "kv.";
+
+/// Pulls extra fields out of the messages using the named captures of regexes
+/// and optionally any key=value pairs in them
+#[derive(Debug, Default)]
+pub(crate) struct Extractor {
+    patterns: Vec<Regex>,
+    kv: Option<Regex>,
+    exceptions: Option<Exceptions>,
+    /// the regexes the fields of a message have to match for it to be kept
+    filters: Vec<(String, Regex)>,
+}
+
+/// Checks a field filter given on the command line is a name=regex
+pub(crate) fn parse_field(text: &str) -> Result<String, String> {
+    match text.split_once('=') {
+        Some((name, _)) if !name.is_empty() => Ok(text.to_string()),
+        _ => Err(format!(
+            "{} is not a field filter, it should be name=regex",
+            text
+        )),
+    }
+}
+
+impl Extractor {
+    pub fn new(patterns: &[String]) -> Result<Self, LogError> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                let regex = Regex::new(pattern).map_err(|err| LogError {
+                    cause: format!("Invalid extract pattern {}: {}", pattern, err),
+                })?;
+                if regex.capture_names().flatten().next().is_none() {
+                    return Err(LogError {
+                        cause: format!(
+                            "The extract pattern {} has no named captures e.g. (?P<name>...)",
+                            pattern
+                        ),
+                    });
+                }
+                Ok(regex)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Extractor {
+            patterns,
+            kv: None,
+            exceptions: None,
+            filters: Vec::new(),
+        })
+    }
+
+    /// Also adds a `kv.<key>` field for each key=value pair found in the messages
+    pub fn with_kv(mut self, kv: bool) -> Self {
+        self.kv = kv.then(|| Regex::new(KV_PATTERN).expect("Invalid key value pattern"));
+        self
+    }
+
+    /// Also adds the `exception`, `minor`, `minor_reason` and `completed`
+    /// fields of any CORBA system exception found in the messages
+    pub fn with_exceptions(mut self, exceptions: Option<Exceptions>) -> Self {
+        self.exceptions = exceptions;
+        self
+    }
+
+    /// Only keeps the messages with fields matching the filters, each given as name=regex
+    pub fn with_filters(mut self, filters: &[String]) -> Result<Self, LogError> {
+        self.filters = filters
+            .iter()
+            .map(|filter| {
+                let (name, pattern) = filter.split_once('=').unwrap_or((filter, ""));
+                let regex = Regex::new(pattern).map_err(|err| LogError {
+                    cause: format!("Invalid field filter {}: {}", filter, err),
+                })?;
+                Ok((name.to_string(), regex))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    /// Checks if the fields extracted from a message match every filter
+    pub fn matches(&self, log: &Log) -> bool {
+        self.filters.iter().all(|(name, regex)| {
+            log.fields
+                .get(name)
+                .is_some_and(|value| regex.is_match(value))
+        })
+    }
+
+    /// The names of the fields that are extracted, sorted in the same order as
+    /// the fields of a message
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .patterns
+            .iter()
+            .flat_map(|pattern| pattern.capture_names().flatten().map(str::to_string))
+            .collect();
+        if self.exceptions.is_some() {
+            names.extend(EXCEPTION_FIELDS.iter().map(|name| name.to_string()));
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Adds the fields found in the message to it, every field is added even
+    /// if its pattern does not match (as an empty string) so they can always be
+    /// used in the output format
+    pub fn extract(&self, log: &mut Log) {
+        if let Some(exceptions) = &self.exceptions {
+            exceptions.extract(log);
+        }
+
+        if let Some(kv) = &self.kv {
+            for captures in kv.captures_iter(&log.message) {
+                let value = unquote(&captures["value"]);
+                log.fields
+                    .insert(format!("{}{}", KV_PREFIX, &captures["key"]), value);
+            }
+        }
+
+        for pattern in &self.patterns {
+            let captures = pattern.captures(&log.message);
+            for name in pattern.capture_names().flatten() {
+                let value = captures
+                    .as_ref()
+                    .and_then(|captures| captures.name(name))
+                    .map(|value| value.as_str().to_string());
+                match value {
+                    Some(value) => {
+                        log.fields.insert(name.to_string(), value);
+                    }
+                    None => {
+                        log.fields.entry(name.to_string()).or_default();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Removes the quotes from around a value along with the escapes in it
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        value[1..value.len() - 1]
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\")
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    #[test]
+    fn extract_fields() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# invoke op=ping id=12
+Pid# 1 Tim# Tue Jul  9 09:09:28 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# reply id=12
+";
+        let extractor = Extractor::new(&[
+            String::from(r"op=(?P<op>\w+)"),
+            String::from(r"id=(?P<id>\d+)"),
+        ])
+        .unwrap();
+        assert_eq!(extractor.names(), vec!["id", "op"]);
+
+        let fields: Vec<Vec<(String, String)>> = logs(text)
+            .into_iter()
+            .map(|mut log| {
+                extractor.extract(&mut log);
+                log.fields.into_iter().collect()
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                vec![
+                    (String::from("id"), String::from("12")),
+                    (String::from("op"), String::from("ping"))
+                ],
+                vec![
+                    (String::from("id"), String::from("12")),
+                    (String::from("op"), String::new())
+                ],
+            ]
+        );
+
+        assert!(Extractor::new(&[String::from("op=\\w+")]).is_err());
+        assert!(Extractor::new(&[String::from("(?P<op>")]).is_err());
+    }
+
+    #[test]
+    fn extract_filters() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# invoke op=ping id=12
+Pid# 1 Tim# Tue Jul  9 09:09:28 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# invoke op=get id=13
+Pid# 1 Tim# Tue Jul  9 09:09:29 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# reply id=13
+";
+        let extractor = Extractor::new(&[String::from(r"op=(?P<op>\w+)")])
+            .unwrap()
+            .with_kv(true)
+            .with_filters(&[String::from("op=^g"), String::from("kv.id=3$")])
+            .unwrap();
+
+        let kept: Vec<String> = logs(text)
+            .into_iter()
+            .filter_map(|mut log| {
+                extractor.extract(&mut log);
+                extractor.matches(&log).then_some(log.message)
+            })
+            .collect();
+        assert_eq!(kept, vec!["invoke op=get id=13"]);
+
+        assert!(Extractor::default()
+            .with_filters(&[String::from("op=(")])
+            .is_err());
+        assert_eq!(parse_field("op=^get"), Ok(String::from("op=^get")));
+        assert_eq!(parse_field("op=").unwrap(), "op=");
+        assert!(parse_field("op").is_err());
+        assert!(parse_field("=get").is_err());
+    }
+
+    #[test]
+    fn extract_key_values() {
+        let text = "Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# connect host=alpha port = 14000, user: \"joe \\\"jj\\\" bloggs\" mode='fast' at 09:09:27 IOR:0001\n";
+        let extractor = Extractor::new(&[]).unwrap().with_kv(true);
+        let mut log = logs(text).remove(0);
+        extractor.extract(&mut log);
+
+        let fields: Vec<(&str, &str)> = log
+            .fields
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("kv.host", "alpha"),
+                ("kv.mode", "fast"),
+                ("kv.port", "14000"),
+                ("kv.user", "joe \"jj\" bloggs"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+use chrono::TimeDelta;
+
+use crate::{gaps::print_message, stats::format_span, Log, LogLevel};
+
+/// The levels that count as an error
+const ERROR_LEVELS: [LogLevel; 4] = [
+    LogLevel::EMERG,
+    LogLevel::ALERT,
+    LogLevel::CRIT,
+    LogLevel::ERROR,
+];
+
+/// An error along with the records logged before it on the same thread
+#[derive(Debug, Clone)]
+struct Candidate {
+    log: Log,
+    before: Vec<Log>,
+}
+
+/// What has been seen of a process
+#[derive(Debug, Default)]
+struct Process {
+    errors: usize,
+    /// the errors that might start the first burst, oldest first
+    window: VecDeque<Candidate>,
+    first: Option<Candidate>,
+}
+
+/// Finds the first error of each process, or the first record of its first
+/// burst of errors, along with the records before it on the same thread
+pub(crate) struct FirstError {
+    before: usize,
+    burst: usize,
+    within: TimeDelta,
+    /// the last records of each thread
+    threads: HashMap<(usize, usize), VecDeque<Log>>,
+    processes: BTreeMap<usize, Process>,
+}
+
+impl FirstError {
+    pub fn new(before: usize, burst: usize, within: TimeDelta) -> Self {
+        FirstError {
+            before,
+            burst: burst.max(1),
+            within,
+            threads: HashMap::new(),
+            processes: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        let process = self.processes.entry(log.pid).or_default();
+        let thread = self.threads.entry((log.pid, log.tid)).or_default();
+
+        if ERROR_LEVELS.contains(&log.level) {
+            process.errors += 1;
+            if process.first.is_none() {
+                process.window.push_back(Candidate {
+                    log: log.clone(),
+                    before: thread.iter().cloned().collect(),
+                });
+                while process
+                    .window
+                    .front()
+                    .is_some_and(|first| log.time - first.log.time > self.within)
+                {
+                    process.window.pop_front();
+                }
+                if process.window.len() >= self.burst {
+                    process.first = process.window.pop_front();
+                    process.window.clear();
+                }
+            }
+        }
+
+        // the records before are only needed until the first error is found
+        if process.first.is_none() && self.before > 0 {
+            thread.push_back(log.clone());
+            if thread.len() > self.before {
+                thread.pop_front();
+            }
+        } else {
+            thread.clear();
+        }
+    }
+
+    /// Prints the first error of each process in the order they happened,
+    /// with the records before it on its thread
+    pub fn print(&self, date_fmt: &str) {
+        let mut found: Vec<(&usize, &Process, &Candidate)> = self
+            .processes
+            .iter()
+            .filter_map(|(pid, process)| {
+                process
+                    .first
+                    .as_ref()
+                    .map(|candidate| (pid, process, candidate))
+            })
+            .collect();
+        found.sort_by_key(|(pid, _, candidate)| (candidate.log.time, **pid));
+
+        for (index, (pid, process, candidate)) in found.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            let burst = if self.burst > 1 {
+                format!(
+                    " starting a burst of {} within {}",
+                    self.burst,
+                    format_span(self.within)
+                )
+            } else {
+                String::new()
+            };
+            println!(
+                "pid {}: first error at {}{}, {} errors in total",
+                pid,
+                candidate.log.time.format(date_fmt),
+                burst,
+                process.errors
+            );
+            if !candidate.before.is_empty() {
+                println!(
+                    "  {} records before it on thread {}:",
+                    candidate.before.len(),
+                    candidate.log.tid
+                );
+            }
+            for log in &candidate.before {
+                print_message(" ", log, date_fmt);
+            }
+            print_message(">", &candidate.log, date_fmt);
+        }
+
+        let clean: BTreeSet<String> = self
+            .processes
+            .iter()
+            .filter(|(_, process)| process.first.is_none())
+            .map(|(pid, process)| {
+                if process.errors > 0 {
+                    format!("{} ({} errors but no burst)", pid, process.errors)
+                } else {
+                    pid.to_string()
+                }
+            })
+            .collect();
+        if !clean.is_empty() {
+            if !found.is_empty() {
+                println!();
+            }
+            println!(
+                "no first error in pid {}",
+                clean.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    fn first_errors(burst: usize) -> Vec<(usize, usize, Vec<usize>, usize)> {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:00 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# a
+Pid# 1 Tim# Tue Jul  9 09:09:01 2024 000000us Tid# 2 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# b
+Pid# 1 Tim# Tue Jul  9 09:09:02 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# c
+Pid# 1 Tim# Tue Jul  9 09:09:03 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# ERROR Msg# d
+Pid# 1 Tim# Tue Jul  9 09:09:30 2024 000000us Tid# 2 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# CRIT Msg# e
+Pid# 1 Tim# Tue Jul  9 09:09:31 2024 000000us Tid# 2 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# ERROR Msg# f
+Pid# 2 Tim# Tue Jul  9 09:09:32 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# WARN Msg# g
+";
+        let mut first_error = FirstError::new(2, burst, TimeDelta::seconds(5));
+        for log in logs(text) {
+            first_error.add(&log);
+        }
+        first_error
+            .processes
+            .iter()
+            .filter_map(|(pid, process)| {
+                process.first.as_ref().map(|candidate| {
+                    (
+                        *pid,
+                        candidate.log.source.line,
+                        candidate.before.iter().map(|log| log.source.line).collect(),
+                        process.errors,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_first_errors() {
+        assert_eq!(first_errors(1), vec![(1, 4, vec![1, 3], 3)]);
+        assert_eq!(first_errors(2), vec![(1, 5, vec![2], 3)]);
+        assert_eq!(first_errors(3), vec![]);
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    reader::{to_line, Records},
+    Log, LogError,
+};
+
+/// How long to wait between checking a followed file for new data
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Identifies the file a path points to so replacing it can be detected
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+fn read_error(path: &str, err: std::io::Error) -> LogError {
+    LogError {
+        cause: format!("Failed to read from {}: {}", path, err),
+    }
+}
+
+fn open(path: &str) -> Result<(BufReader<fs::File>, u64), LogError> {
+    let handle = fs::File::open(path).map_err(|err| read_error(path, err))?;
+    let id = handle.metadata().map(|m| file_id(&m)).unwrap_or(0);
+    Ok((BufReader::new(handle), id))
+}
+
+/// A log file that is still being written to.
+///
+/// Like `tail -F` the path is followed rather than the file, so if the log is
+/// rotated (the path now points to a different file) the rest of the old file
+/// is read before moving on to the new one, and if the log is truncated reading
+/// starts again from the beginning.
+pub(crate) struct FollowedLog {
+    path: String,
+    reader: BufReader<fs::File>,
+    id: u64,
+    /// bytes of a line that has not been completely written yet
+    partial: Vec<u8>,
+    records: Records,
+    /// messages that are complete and are returned before reading any more
+    ready: VecDeque<Log>,
+    /// when data was last read, used to flush a message once nothing more is written
+    last_read: Instant,
+    timeout: Duration,
+}
+
+impl FollowedLog {
+    /// Starts following a file from its current end
+    pub fn new(path: &str, timeout: Duration) -> Result<Self, LogError> {
+        let (reader, id) = open(path)?;
+        let mut log = FollowedLog {
+            path: path.to_string(),
+            reader,
+            id,
+            partial: Vec::new(),
+            records: Records::new(path),
+            ready: VecDeque::new(),
+            last_read: Instant::now(),
+            timeout,
+        };
+        log.skip_to_end()?;
+        Ok(log)
+    }
+
+    fn error(&self, err: std::io::Error) -> LogError {
+        read_error(&self.path, err)
+    }
+
+    /// Skips over everything that is already in the file counting the lines so
+    /// the messages that follow have the right line numbers
+    fn skip_to_end(&mut self) -> Result<(), LogError> {
+        let mut buffer = [0; 64 * 1024];
+        let (mut lines, mut offset, mut line_start) = (0, 0, 0);
+        loop {
+            let size = match self.reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(size) => size,
+                Err(err) => return Err(self.error(err)),
+            };
+            for (index, byte) in buffer[..size].iter().enumerate() {
+                if *byte == b'\n' {
+                    lines += 1;
+                    line_start = offset + index + 1;
+                }
+            }
+            offset += size;
+        }
+
+        // any unfinished line is left to be read with the rest of its message
+        self.reader
+            .seek(SeekFrom::Start(line_start as u64))
+            .map_err(|err| self.error(err))?;
+        self.records.seek(Some(lines), line_start);
+        Ok(())
+    }
+
+    /// Queues the messages held waiting for more data, the unfinished line may
+    /// start a message of its own
+    fn flush_held(&mut self) -> Result<(), LogError> {
+        if !self.partial.is_empty() {
+            let line = to_line(std::mem::take(&mut self.partial), &self.records)?;
+            if let Some(log) = self.records.push_line(line).transpose()? {
+                self.ready.push_back(log);
+            }
+        }
+        if let Some(log) = self.records.flush().transpose()? {
+            self.ready.push_back(log);
+        }
+        Ok(())
+    }
+
+    /// Checks if the path now points at a different or truncated file, any
+    /// messages left in the old file are queued to be returned
+    fn check_replaced(&mut self) -> Result<(), LogError> {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            // the file has been moved and the new one is not there yet
+            return Ok(());
+        };
+
+        let position = match self.reader.stream_position() {
+            Ok(position) => position,
+            Err(err) => return Err(self.error(err)),
+        };
+
+        if file_id(&metadata) == self.id && metadata.len() >= position {
+            return Ok(());
+        }
+
+        // the rest of the old file is complete so anything held can be flushed
+        self.flush_held()?;
+
+        (self.reader, self.id) = open(&self.path)?;
+        self.records = Records::new(&self.path);
+        Ok(())
+    }
+
+    /// Returns the next message if one is available without waiting for more data
+    pub fn poll(&mut self) -> Option<Result<Log, LogError>> {
+        if let Some(log) = self.ready.pop_front() {
+            return Some(Ok(log));
+        }
+
+        loop {
+            let size = match self.reader.read_until(b'\n', &mut self.partial) {
+                Ok(size) => size,
+                Err(err) => return Some(Err(self.error(err))),
+            };
+
+            if size == 0 {
+                break;
+            }
+            self.last_read = Instant::now();
+
+            if !self.partial.ends_with(b"\n") {
+                // the writer is part way through a line
+                break;
+            }
+
+            let line = match to_line(std::mem::take(&mut self.partial), &self.records) {
+                Ok(line) => line,
+                Err(err) => return Some(Err(err)),
+            };
+            if let Some(log) = self.records.push_line(line) {
+                return Some(log);
+            }
+        }
+
+        if let Err(err) = self.check_replaced() {
+            return Some(Err(err));
+        }
+        if let Some(log) = self.ready.pop_front() {
+            return Some(Ok(log));
+        }
+
+        // once nothing has been written for a while whatever is held is
+        // complete, even a last line the writer never finished
+        if (!self.partial.is_empty() || self.records.has_pending())
+            && self.last_read.elapsed() >= self.timeout
+        {
+            if let Err(err) = self.flush_held() {
+                return Some(Err(err));
+            }
+            return self.ready.pop_front().map(Ok);
+        }
+
+        None
+    }
+}
+
+/// Follows a number of files printing messages as they are written
+pub(crate) struct Follow {
+    logs: Vec<FollowedLog>,
+    next: usize,
+}
+
+impl Follow {
+    pub fn new(logs: Vec<FollowedLog>) -> Self {
+        Follow { logs, next: 0 }
+    }
+
+    /// Returns the next message from any of the files if one is available
+    /// without waiting for more data
+    pub fn poll(&mut self) -> Option<Result<Log, LogError>> {
+        for _ in 0..self.logs.len() {
+            let index = self.next;
+            self.next = (self.next + 1) % self.logs.len();
+            if let Some(log) = self.logs[index].poll() {
+                return Some(log);
+            }
+        }
+        None
+    }
+
+    /// Waits for a while before the files are polled again
+    pub fn wait(&self) {
+        sleep(POLL_INTERVAL);
+    }
+}
+
+impl Iterator for Follow {
+    type Item = Result<Log, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.logs.is_empty() {
+            return None;
+        }
+
+        loop {
+            if let Some(log) = self.poll() {
+                return Some(log);
+            }
+            self.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{record, TempDir};
+    use std::io::Write;
+
+    fn append(path: &std::path::Path, text: &str) {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    /// Makes it look like nothing has been written to a log for longer than
+    /// its timeout
+    fn wait(log: &mut FollowedLog) {
+        log.last_read = Instant::now() - log.timeout;
+    }
+
+    #[test]
+    fn followed_log_partial_writes() {
+        let dir = TempDir::new("follow");
+        let path = dir.join("app.log");
+        fs::write(&path, record(1).message("old")).unwrap();
+
+        let name = path.to_string_lossy().to_string();
+        let mut log = FollowedLog::new(&name, Duration::from_secs(60)).unwrap();
+        assert!(log.poll().is_none());
+
+        // a message half way through being written is held
+        let second = record(2).message("second");
+        append(&path, &second[..20]);
+        assert!(log.poll().is_none());
+        append(&path, &second[20..]);
+        assert!(log.poll().is_none());
+
+        // it is returned once the next one starts
+        append(&path, &record(3).message("third"));
+        let found = log.poll().unwrap().unwrap();
+        assert_eq!(found.message, "second");
+        assert_eq!(found.source.line, 2);
+
+        // or once nothing has been written for a while
+        assert!(log.poll().is_none());
+        wait(&mut log);
+        assert_eq!(log.poll().unwrap().unwrap().message, "third");
+
+        // even if the writer stopped part way through the last line
+        let fourth = record(4).message("fourth");
+        append(&path, &fourth[..fourth.len() - 1]);
+        assert!(log.poll().is_none());
+        wait(&mut log);
+        let found = log.poll().unwrap().unwrap();
+        assert_eq!(found.message, "fourth");
+        assert_eq!(found.source.line, 4);
+        assert!(log.poll().is_none());
+
+        // truncating the file starts reading from the start again
+        fs::write(&path, "").unwrap();
+        assert!(log.poll().is_none());
+        append(&path, &record(5).message("fifth"));
+        assert!(log.poll().is_none());
+        wait(&mut log);
+        let found = log.poll().unwrap().unwrap();
+        assert_eq!(found.message, "fifth");
+        assert_eq!(found.source.line, 1);
+    }
+
+    #[test]
+    fn followed_log_rotated_mid_message() {
+        let dir = TempDir::new("follow-rotated");
+        let path = dir.join("app.log");
+        fs::write(&path, "").unwrap();
+
+        let name = path.to_string_lossy().to_string();
+        let mut log = FollowedLog::new(&name, Duration::from_secs(60)).unwrap();
+
+        // the old file ends part way through a message after a complete one
+        let second = record(2).message("second");
+        append(
+            &path,
+            &(record(1).message("first") + &second[..second.len() - 1]),
+        );
+        assert!(log.poll().is_none());
+
+        fs::rename(&path, dir.join("app.log.1")).unwrap();
+        fs::write(&path, record(3).message("third")).unwrap();
+
+        assert_eq!(log.poll().unwrap().unwrap().message, "first");
+        assert_eq!(log.poll().unwrap().unwrap().message, "second");
+        assert!(log.poll().is_none());
+        let found = log.records.flush().unwrap().unwrap();
+        assert_eq!(found.message, "third");
+        assert_eq!(found.source.line, 1);
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use chrono::TimeDelta;
+use clap::ValueEnum;
+
+use crate::{stats::format_span, Log};
+
+/// What the messages are grouped by when looking for gaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Per {
+    /// Each process
+    Process,
+    /// Each thread of each process
+    Thread,
+    /// Both each process and each thread
+    Both,
+}
+
+/// Who a gap was found in, a whole process or one of its threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Owner {
+    Process(usize),
+    Thread(usize, usize),
+}
+
+/// A period of time where a process or thread logged nothing while others did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Gap {
+    owner: Owner,
+    /// the last message before the gap
+    before: Log,
+    /// the first message after the gap, None if it never logged again
+    after: Option<Log>,
+    /// how long the gap lasted
+    length: TimeDelta,
+    /// how many messages others logged during the gap
+    others: usize,
+}
+
+/// The last message seen from a process or thread along with how many messages
+/// had been seen in total at that point
+struct Last {
+    log: Log,
+    seen: usize,
+}
+
+/// Finds the periods of time where a process or a thread logged nothing for at
+/// least the threshold while other processes or threads kept logging, e.g. a
+/// hung dispatcher thread or a deadlock.
+pub(crate) struct Gaps {
+    threshold: TimeDelta,
+    per: Per,
+    seen: usize,
+    latest: Option<Log>,
+    last: HashMap<Owner, Last>,
+    gaps: Vec<Gap>,
+}
+
+impl Gaps {
+    pub fn new(threshold: TimeDelta, per: Per) -> Self {
+        Gaps {
+            threshold,
+            per,
+            seen: 0,
+            latest: None,
+            last: HashMap::new(),
+            gaps: Vec::new(),
+        }
+    }
+
+    fn owners(&self, log: &Log) -> Vec<Owner> {
+        match self.per {
+            Per::Process => vec![Owner::Process(log.pid)],
+            Per::Thread => vec![Owner::Thread(log.pid, log.tid)],
+            Per::Both => vec![Owner::Process(log.pid), Owner::Thread(log.pid, log.tid)],
+        }
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        self.seen += 1;
+        for owner in self.owners(log) {
+            let last = self.last.insert(
+                owner,
+                Last {
+                    log: log.clone(),
+                    seen: self.seen,
+                },
+            );
+            let Some(last) = last else { continue };
+
+            let length = log.time - last.log.time;
+            let others = self.seen - last.seen - 1;
+            if length >= self.threshold && others > 0 {
+                self.gaps.push(Gap {
+                    owner,
+                    before: last.log,
+                    after: Some(log.clone()),
+                    length,
+                    others,
+                });
+            }
+        }
+
+        if self
+            .latest
+            .as_ref()
+            .is_none_or(|latest| latest.time <= log.time)
+        {
+            self.latest = Some(log.clone());
+        }
+    }
+
+    /// Returns the gaps found in the order they started, including the ones
+    /// where a process or thread never logged again before the end
+    pub fn finish(mut self) -> Vec<Gap> {
+        if let Some(latest) = &self.latest {
+            for (owner, last) in self.last.drain() {
+                let length = latest.time - last.log.time;
+                let others = self.seen - last.seen;
+                if length >= self.threshold && others > 0 {
+                    self.gaps.push(Gap {
+                        owner,
+                        before: last.log,
+                        after: None,
+                        length,
+                        others,
+                    });
+                }
+            }
+        }
+
+        self.gaps.sort_by(|a, b| {
+            (a.before.time, &a.before.source.input, a.before.source.line)
+                .cmp(&(b.before.time, &b.before.source.input, b.before.source.line))
+                .then(a.owner.cmp(&b.owner))
+        });
+        self.gaps
+    }
+}
+
+pub(crate) fn print_message(label: &str, log: &Log, date_fmt: &str) {
+    println!(
+        "  {} {} {}: {}: {}",
+        label,
+        log.time.format(date_fmt),
+        log.source,
+        log.level,
+        log.message.lines().next().unwrap_or_default()
+    );
+}
+
+/// Prints each gap along with the message before and after it
+pub(crate) fn print(gaps: &[Gap], date_fmt: &str) {
+    for (index, gap) in gaps.iter().enumerate() {
+        if index > 0 {
+            println!();
+        }
+        let owner = match gap.owner {
+            Owner::Process(pid) => format!("pid {}", pid),
+            Owner::Thread(pid, tid) => format!("pid {} tid {}", pid, tid),
+        };
+        let until = if gap.after.is_some() {
+            ""
+        } else {
+            " until the end"
+        };
+        println!(
+            "{} logged nothing for {}{} while {} other messages were logged",
+            owner,
+            format_span(gap.length),
+            until,
+            gap.others
+        );
+        print_message("last:", &gap.before, date_fmt);
+        if let Some(after) = &gap.after {
+            print_message("next:", after, date_fmt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    fn gaps(per: Per) -> Vec<(Owner, i64, Option<usize>)> {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:00 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# a
+Pid# 1 Tim# Tue Jul  9 09:09:01 2024 000000us Tid# 2 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# b
+Pid# 2 Tim# Tue Jul  9 09:09:02 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# c
+Pid# 1 Tim# Tue Jul  9 09:09:04 2024 000000us Tid# 2 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# d
+Pid# 1 Tim# Tue Jul  9 09:09:10 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# e
+Pid# 2 Tim# Tue Jul  9 09:09:20 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# f
+";
+        let mut gaps = Gaps::new(TimeDelta::seconds(5), per);
+        for log in logs(text) {
+            gaps.add(&log);
+        }
+        gaps.finish()
+            .into_iter()
+            .map(|gap| {
+                (
+                    gap.owner,
+                    gap.length.num_seconds(),
+                    gap.after.map(|log| log.source.line),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn find_gaps() {
+        assert_eq!(
+            gaps(Per::Process),
+            vec![
+                (Owner::Process(2), 18, Some(6)),
+                (Owner::Process(1), 10, None),
+            ]
+        );
+        assert_eq!(
+            gaps(Per::Thread),
+            vec![
+                (Owner::Thread(1, 1), 10, Some(5)),
+                (Owner::Thread(2, 1), 18, Some(6)),
+                (Owner::Thread(1, 2), 16, None),
+                (Owner::Thread(1, 1), 10, None),
+            ]
+        );
+    }
+}
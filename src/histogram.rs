@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use clap::ValueEnum;
+
+use crate::{
+    stats::{format_span, parse_duration},
+    Log, LogError, LogLevel,
+};
+
+/// How wide the bars are when the busiest bucket is drawn
+const BAR_WIDTH: usize = 60;
+/// When picking the bucket size automatically the smallest size that gives at
+/// most this many buckets is used
+const AUTO_BUCKETS: i64 = 60;
+/// The most buckets a histogram can have, as the empty ones are printed too
+const MAX_BUCKETS: i64 = 100_000;
+/// The bucket sizes that can be picked automatically in seconds
+const AUTO_SIZES: [i64; 15] = [
+    1, 2, 5, 10, 15, 30, 60, 120, 300, 600, 900, 1800, 3600, 21600, 86400,
+];
+/// The blocks a sparkline is drawn with from the lowest to the highest
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// The character each level is drawn with when the bars are stacked by level
+const LEVEL_MARKS: [char; 7] = ['!', 'A', 'C', 'E', 'W', 'I', 'D'];
+
+/// How long a period of time each bucket of the histogram covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BucketSize {
+    /// Pick a size that gives a reasonable number of buckets for the time the messages span
+    Auto,
+    /// A fixed size in microseconds
+    Fixed(i64),
+}
+
+/// Parses the size of the histogram buckets, either `auto` or a length of time e.g. `1s`, `5m`
+pub(crate) fn parse_bucket_size(text: &str) -> Result<BucketSize, String> {
+    if text == "auto" {
+        return Ok(BucketSize::Auto);
+    }
+
+    match parse_duration(text)?.num_microseconds() {
+        Some(micros) if micros > 0 => Ok(BucketSize::Fixed(micros)),
+        _ => Err(format!("{} is not a valid bucket size", text)),
+    }
+}
+
+/// How the histogram is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Chart {
+    /// A bar for each bucket
+    Bars,
+    /// A single line with a block for each bucket
+    Sparkline,
+    /// A row of comma separated values for each bucket
+    Csv,
+}
+
+/// The number of messages at each level
+pub(crate) type Counts = [usize; LogLevel::ALL.len()];
+
+pub(crate) fn level_index(level: &LogLevel) -> usize {
+    LogLevel::ALL
+        .iter()
+        .position(|other| other == level)
+        .unwrap_or_default()
+}
+
+/// Counts the number of messages in each period of time
+#[derive(Debug)]
+pub(crate) struct Histogram {
+    size: BucketSize,
+    /// The number of messages at each level in each bucket, keyed by the start
+    /// of the bucket in units of the bucket size (or seconds for automatically
+    /// sized buckets)
+    buckets: BTreeMap<i64, Counts>,
+}
+
+impl Histogram {
+    pub fn new(size: BucketSize) -> Self {
+        Histogram {
+            size,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    fn resolution(&self) -> i64 {
+        match self.size {
+            BucketSize::Auto => 1_000_000,
+            BucketSize::Fixed(micros) => micros,
+        }
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        let key = log
+            .time
+            .and_utc()
+            .timestamp_micros()
+            .div_euclid(self.resolution());
+        self.buckets.entry(key).or_default()[level_index(&log.level)] += 1;
+    }
+
+    /// Works out the size of each bucket in microseconds and the messages in
+    /// each bucket from the first to the last, including the empty ones
+    /// between them, too small a bucket size for the time the messages span
+    /// gives too many buckets to print
+    pub fn buckets(&self) -> Result<(i64, Vec<(i64, Counts)>), LogError> {
+        let (Some(first), Some(last)) = (self.buckets.keys().next(), self.buckets.keys().last())
+        else {
+            return Ok((self.resolution(), Vec::new()));
+        };
+
+        // automatically sized buckets are counted a second at a time and then
+        // combined into the size picked once the time span is known
+        let (size, scale) = match self.size {
+            BucketSize::Fixed(micros) => (micros, 1),
+            BucketSize::Auto => {
+                let seconds = AUTO_SIZES
+                    .iter()
+                    .copied()
+                    .find(|size| (last - first) / size < AUTO_BUCKETS)
+                    .unwrap_or_else(|| ((last - first) / AUTO_BUCKETS + 1).max(1));
+                (seconds * 1_000_000, seconds)
+            }
+        };
+
+        let mut combined: BTreeMap<i64, Counts> = BTreeMap::new();
+        for (key, counts) in &self.buckets {
+            let bucket = combined.entry(key.div_euclid(scale)).or_default();
+            for (total, count) in bucket.iter_mut().zip(counts) {
+                *total += count;
+            }
+        }
+
+        let (first, last) = (first.div_euclid(scale), last.div_euclid(scale));
+        if last - first >= MAX_BUCKETS {
+            return Err(LogError {
+                cause: format!(
+                    "{} buckets of {} is too many, use a bigger --histogram size",
+                    last - first + 1,
+                    format_span(TimeDelta::microseconds(size))
+                ),
+            });
+        }
+
+        let buckets = (first..=last)
+            .map(|key| (key * size, combined.get(&key).copied().unwrap_or_default()))
+            .collect();
+        Ok((size, buckets))
+    }
+
+    /// Prints the histogram, stacked by level if `by_level` is set
+    pub fn print(&self, chart: Chart, by_level: bool, date_fmt: &str) -> Result<(), LogError> {
+        let (size, buckets) = self.buckets()?;
+        let time = |micros: i64| -> String {
+            DateTime::from_timestamp_micros(micros)
+                .map(|time| time.naive_utc())
+                .unwrap_or(NaiveDateTime::MIN)
+                .format(date_fmt)
+                .to_string()
+        };
+
+        match chart {
+            Chart::Csv => {
+                let mut header = String::from("time,count");
+                if by_level {
+                    for level in LogLevel::ALL {
+                        header.push_str(&format!(",{}", level));
+                    }
+                }
+                println!("{}", header);
+                for (start, counts) in &buckets {
+                    let mut row = format!("\"{}\",{}", time(*start), counts.iter().sum::<usize>());
+                    if by_level {
+                        for count in counts {
+                            row.push_str(&format!(",{}", count));
+                        }
+                    }
+                    println!("{}", row);
+                }
+            }
+            Chart::Sparkline => {
+                let (Some((first, _)), Some((last, _))) = (buckets.first(), buckets.last()) else {
+                    return Ok(());
+                };
+                println!(
+                    "{} - {} ({} per character)",
+                    time(*first),
+                    time(*last + size),
+                    format_span(TimeDelta::microseconds(size))
+                );
+                if by_level {
+                    for (index, level) in LogLevel::ALL.iter().enumerate() {
+                        let counts: Vec<usize> =
+                            buckets.iter().map(|(_, counts)| counts[index]).collect();
+                        if counts.iter().any(|count| *count > 0) {
+                            println!("{:<5}  {}", level.to_string(), sparkline(&counts));
+                        }
+                    }
+                } else {
+                    let counts: Vec<usize> = buckets
+                        .iter()
+                        .map(|(_, counts)| counts.iter().sum())
+                        .collect();
+                    println!("{}", sparkline(&counts));
+                }
+            }
+            Chart::Bars => {
+                if by_level {
+                    let legend: Vec<String> = LogLevel::ALL
+                        .iter()
+                        .zip(LEVEL_MARKS)
+                        .map(|(level, mark)| format!("{}={}", mark, level))
+                        .collect();
+                    println!("{}", legend.join(" "));
+                }
+                let max = buckets
+                    .iter()
+                    .map(|(_, counts)| counts.iter().sum::<usize>())
+                    .max()
+                    .unwrap_or_default();
+                for (start, counts) in &buckets {
+                    println!(
+                        "{}  {:>7} {}",
+                        time(*start),
+                        counts.iter().sum::<usize>(),
+                        bar(counts, max, by_level)
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Scales a count to the width of a bar, anything above zero is at least one character
+fn scale(count: usize, max: usize) -> usize {
+    if count == 0 || max == 0 {
+        0
+    } else {
+        ((count * BAR_WIDTH) as f64 / max as f64).round().max(1.0) as usize
+    }
+}
+
+/// Draws the bar for a bucket, if stacked each level gets its own part of the bar
+fn bar(counts: &Counts, max: usize, by_level: bool) -> String {
+    if !by_level {
+        return "#".repeat(scale(counts.iter().sum(), max));
+    }
+
+    // each part of the bar ends where the running total does so rounding does
+    // not add up along the bar
+    let mut bar = String::new();
+    let mut total = 0;
+    for (count, mark) in counts.iter().zip(LEVEL_MARKS) {
+        if *count == 0 {
+            continue;
+        }
+        total += count;
+        let width = scale(total, max).max(bar.chars().count() + 1);
+        while bar.chars().count() < width {
+            bar.push(mark);
+        }
+    }
+    bar
+}
+
+/// Draws a line of blocks whose heights follow the counts
+fn sparkline(counts: &[usize]) -> String {
+    let max = counts.iter().copied().max().unwrap_or_default();
+    counts
+        .iter()
+        .map(|count| match (*count, max) {
+            (0, _) => ' ',
+            (count, max) => SPARKS[(count * SPARKS.len() - 1) / max],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{logs, record};
+
+    fn histogram(size: &str, seconds: &[(usize, &str)]) -> Histogram {
+        let text: String = seconds
+            .iter()
+            .map(|(second, level)| record(*second).level(level).message("test"))
+            .collect();
+        let mut histogram = Histogram::new(parse_bucket_size(size).unwrap());
+        for log in logs(&text) {
+            histogram.add(&log);
+        }
+        histogram
+    }
+
+    fn totals(histogram: &Histogram) -> (i64, Vec<usize>) {
+        let (size, buckets) = histogram.buckets().unwrap();
+        (
+            size / 1_000_000,
+            buckets
+                .iter()
+                .map(|(_, counts)| counts.iter().sum())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let seconds = [
+            (0, "INFO"),
+            (1, "INFO"),
+            (1, "ERROR"),
+            (4, "ERROR"),
+            (10, "DEBUG"),
+        ];
+
+        assert_eq!(
+            totals(&histogram("1s", &seconds)),
+            (1, vec![1, 2, 0, 0, 1, 0, 0, 0, 0, 0, 1])
+        );
+        assert_eq!(totals(&histogram("5s", &seconds)), (5, vec![4, 0, 1]));
+        assert_eq!(totals(&histogram("1m", &seconds)), (60, vec![5]));
+
+        // a few seconds of messages are shown a second at a time, a couple of hours a few minutes at a time
+        assert_eq!(totals(&histogram("auto", &seconds)).0, 1);
+        assert_eq!(
+            totals(&histogram("auto", &[(0, "INFO"), (7199, "INFO")])),
+            (120, [vec![1], vec![0; 58], vec![1]].concat())
+        );
+
+        let (_, buckets) = histogram("5s", &seconds).buckets().unwrap();
+        assert_eq!(buckets[0].1, [0, 0, 0, 2, 0, 2, 0]);
+        assert_eq!(
+            bar(&buckets[0].1, 4, true),
+            "E".repeat(30) + &"I".repeat(30)
+        );
+        assert_eq!(sparkline(&[0, 1, 4, 8]), " ▁▄█");
+        assert_eq!(sparkline(&[2, 2]), "██");
+
+        assert!(histogram("1ms", &[(0, "INFO"), (7199, "INFO")])
+            .buckets()
+            .is_err());
+
+        assert!(parse_bucket_size("0s").is_err());
+        assert!(parse_bucket_size("fast").is_err());
+    }
+}
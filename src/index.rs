@@ -0,0 +1,365 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    time::UNIX_EPOCH,
+};
+
+use chrono::NaiveDateTime;
+
+use crate::{input, reader::LogReader, Log, LogError, LogStream};
+
+/// The first line of every index file, used to spot files that are not indexes
+const INDEX_HEADER: &str = "vislog-index 1";
+/// Added to the name of a log to get the name of its index
+const INDEX_EXTENSION: &str = ".idx";
+
+/// A point in a log that reading can start from
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Checkpoint {
+    offset: usize,
+    line: usize,
+    /// the latest time of any message before this point
+    latest_before: i64,
+    /// the earliest time of any message from this point on
+    earliest_after: i64,
+}
+
+/// The part of a log that needs to be read to find the messages in a time range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Window {
+    pub offset: usize,
+    /// the number of lines before the window, None if it is not known
+    pub line: Option<usize>,
+    pub end: Option<usize>,
+}
+
+fn index_path(path: &str) -> String {
+    format!("{}{}", path, INDEX_EXTENSION)
+}
+
+fn error(path: &str, err: impl std::fmt::Display) -> LogError {
+    LogError {
+        cause: format!("Unable to index {}: {}", path, err),
+    }
+}
+
+/// The size and modification time of a log, if either changes the index is out of date
+fn stamp(path: &str) -> Result<String, LogError> {
+    let metadata = fs::metadata(path).map_err(|err| error(path, err))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    Ok(format!(
+        "{} {}.{:09}",
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos()
+    ))
+}
+
+fn micros(log: &Log) -> i64 {
+    log.time.and_utc().timestamp_micros()
+}
+
+fn checkpoints<I>(logs: I, every: usize) -> Result<Vec<Checkpoint>, LogError>
+where
+    I: Iterator<Item = Result<Log, LogError>>,
+{
+    let mut checkpoints: Vec<Checkpoint> = Vec::new();
+    let mut latest = i64::MIN;
+
+    for (count, log) in logs.enumerate() {
+        let log = log?;
+        let time = micros(&log);
+
+        if count % every == 0 {
+            checkpoints.push(Checkpoint {
+                offset: log.source.offset,
+                line: log.source.line - 1,
+                latest_before: latest,
+                earliest_after: time,
+            });
+        }
+
+        let last = checkpoints.len() - 1;
+        checkpoints[last].earliest_after = checkpoints[last].earliest_after.min(time);
+        latest = latest.max(time);
+    }
+
+    // so far each checkpoint only has the earliest time up until the next one
+    for index in (0..checkpoints.len().saturating_sub(1)).rev() {
+        checkpoints[index].earliest_after = checkpoints[index]
+            .earliest_after
+            .min(checkpoints[index + 1].earliest_after);
+    }
+
+    Ok(checkpoints)
+}
+
+/// Parses the number of messages between each checkpoint, which has to be at least one
+pub(crate) fn parse_every(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(every) if every > 0 => Ok(every),
+        _ => Err(format!("{} is not a valid number of messages", text)),
+    }
+}
+
+/// Writes an index for a log to a file next to it with a checkpoint every `every` messages
+pub(crate) fn build(path: &str, every: usize) -> Result<String, LogError> {
+    // an index written by an older version may have asked for none
+    let every = every.max(1);
+    let handle = fs::File::open(path).map_err(|err| error(path, err))?;
+    let mut reader = BufReader::new(handle);
+    if input::is_compressed(reader.fill_buf().map_err(|err| error(path, err))?) {
+        return Err(error(path, "compressed logs can not be indexed"));
+    }
+
+    let stamp = stamp(path)?;
+    let checkpoints = checkpoints(LogReader::new(reader, path), every)?;
+
+    let mut text = format!("{}\nevery {}\n{}\n", INDEX_HEADER, every, stamp);
+    for checkpoint in &checkpoints {
+        text.push_str(&format!(
+            "{} {} {} {}\n",
+            checkpoint.offset, checkpoint.line, checkpoint.latest_before, checkpoint.earliest_after
+        ));
+    }
+
+    let output = index_path(path);
+    let mut file = fs::File::create(&output).map_err(|err| error(&output, err))?;
+    file.write_all(text.as_bytes())
+        .map_err(|err| error(&output, err))?;
+    Ok(output)
+}
+
+fn parse_checkpoint(line: &str) -> Option<Checkpoint> {
+    let mut fields = line.split(' ').map(str::parse::<i64>);
+    Some(Checkpoint {
+        offset: fields.next()?.ok()? as usize,
+        line: fields.next()?.ok()? as usize,
+        latest_before: fields.next()?.ok()?,
+        earliest_after: fields.next()?.ok()?,
+    })
+}
+
+/// An index read from disk
+struct Index {
+    /// how many messages there are between each checkpoint
+    every: usize,
+    /// false if the log has changed since the index was written
+    fresh: bool,
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// Reads the index of a log, None is returned if there is no valid index
+fn load(path: &str) -> Result<Option<Index>, LogError> {
+    let Ok(text) = fs::read_to_string(index_path(path)) else {
+        return Ok(None);
+    };
+
+    // an index cut short part way through a line can't be trusted
+    let mut lines = text.lines();
+    if !text.ends_with('\n') || lines.next() != Some(INDEX_HEADER) {
+        return Ok(None);
+    }
+    let Some(every) = lines
+        .next()
+        .and_then(|line| line.strip_prefix("every "))
+        .and_then(|every| every.parse().ok())
+    else {
+        return Ok(None);
+    };
+    let fresh = lines.next() == Some(stamp(path)?.as_str());
+
+    Ok(lines
+        .map(parse_checkpoint)
+        .collect::<Option<Vec<_>>>()
+        .map(|checkpoints| Index {
+            every,
+            fresh,
+            checkpoints,
+        }))
+}
+
+fn seconds(micros: i64) -> i64 {
+    micros.div_euclid(1_000_000)
+}
+
+fn find_window(
+    checkpoints: &[Checkpoint],
+    after: Option<NaiveDateTime>,
+    before: Option<NaiveDateTime>,
+) -> Window {
+    // times are compared to the second in the same way as the --after and --before filters
+    let mut start = &Checkpoint {
+        offset: 0,
+        line: 0,
+        latest_before: i64::MIN,
+        earliest_after: i64::MIN,
+    };
+    if let Some(after) = after.map(|time| time.and_utc().timestamp()) {
+        for checkpoint in checkpoints {
+            if checkpoint.latest_before != i64::MIN && seconds(checkpoint.latest_before) >= after {
+                break;
+            }
+            start = checkpoint;
+        }
+    }
+
+    let end = before.and_then(|before| {
+        let before = before.and_utc().timestamp();
+        checkpoints
+            .iter()
+            .find(|checkpoint| {
+                checkpoint.offset > start.offset && seconds(checkpoint.earliest_after) > before
+            })
+            .map(|checkpoint| checkpoint.offset)
+    });
+
+    Window {
+        offset: start.offset,
+        line: Some(start.line),
+        end,
+    }
+}
+
+/// Uses the index of a log to find the part of it that contains the messages
+/// in a time range.
+///
+/// If the index is out of date it is rebuilt first, None is returned if the log
+/// has never been indexed or the index can't be rebuilt so the log is searched
+/// some other way.
+pub(crate) fn window(
+    path: &str,
+    after: Option<NaiveDateTime>,
+    before: Option<NaiveDateTime>,
+) -> Result<Option<Window>, LogError> {
+    let index = match load(path)? {
+        Some(index) if !index.fresh => {
+            if let Err(err) = build(path, index.every) {
+                eprintln!("WARNING: {}, not using the index", err.cause);
+                return Ok(None);
+            }
+            load(path)?
+        }
+        index => index,
+    };
+
+    Ok(index.map(|index| find_window(&index.checkpoints, after, before)))
+}
+
+/// Opens the part of a log within a window as a stream of log messages
+pub(crate) fn open(path: &str, window: Window) -> Result<LogStream, LogError> {
+    let mut handle = fs::File::open(path).map_err(|err| LogError {
+        cause: format!("Unable to open {}: {}", path, err),
+    })?;
+    handle
+        .seek(SeekFrom::Start(window.offset as u64))
+        .map_err(|err| error(path, err))?;
+
+    let logs =
+        LogReader::new(BufReader::new(handle), path).with_position(window.line, window.offset);
+    match window.end {
+        Some(end) => Ok(Box::new(logs.take_while(move |log| match log {
+            Ok(log) => log.source.offset < end,
+            Err(_) => true,
+        }))),
+        None => Ok(Box::new(logs)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{record, TempDir};
+
+    fn time(second: usize) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(
+            &format!("Tue Jul  9 09:00:{:02} 2024 000000us", second),
+            "%a %b %e %H:%M:%S %Y %fus",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn index_window() {
+        let seconds = [1, 2, 3, 4, 9, 5, 6, 7, 8, 10, 11, 12];
+        let text: String = seconds
+            .iter()
+            .map(|second| record(*second).message(&second.to_string()))
+            .collect();
+
+        let dir = TempDir::new("index");
+        let path = dir.join("app.log").to_string_lossy().to_string();
+        fs::write(&path, &text).unwrap();
+
+        let messages = |after: Option<usize>, before: Option<usize>| -> Vec<String> {
+            let window = window(&path, after.map(time), before.map(time))
+                .unwrap()
+                .unwrap();
+            open(&path, window)
+                .unwrap()
+                .map(|log| log.unwrap().message)
+                .collect()
+        };
+
+        assert_eq!(window(&path, None, None).unwrap(), None);
+        build(&path, 3).unwrap();
+
+        // the 9 that is out of order means reading has to start before it
+        assert_eq!(
+            messages(Some(6), None),
+            vec!["4", "9", "5", "6", "7", "8", "10", "11", "12"]
+        );
+        assert_eq!(messages(Some(11), None), vec!["10", "11", "12"]);
+        assert_eq!(messages(None, Some(3)), vec!["1", "2", "3"]);
+        assert_eq!(
+            messages(Some(5), Some(7)),
+            vec!["4", "9", "5", "6", "7", "8"]
+        );
+
+        // changing the log means the index is rebuilt
+        fs::write(&path, format!("{}{}", text, text.lines().last().unwrap())).unwrap();
+        assert_eq!(messages(Some(12), None), vec!["10", "11", "12", "12"]);
+    }
+
+    #[test]
+    fn index_damaged() {
+        let text: String = (1..=6)
+            .map(|second| record(second).message("test"))
+            .collect();
+        let dir = TempDir::new("index-damaged");
+        let path = dir.join("app.log").to_string_lossy().to_string();
+        fs::write(&path, &text).unwrap();
+
+        // anything that isn't a whole index is ignored so the log is searched instead
+        build(&path, 2).unwrap();
+        let index = fs::read_to_string(index_path(&path)).unwrap();
+        for damaged in [
+            String::new(),
+            index.replacen(INDEX_HEADER, "vislog-index 0", 1),
+            index.replacen("every 2", "every", 1),
+            index[..index.len() - 4].to_string(),
+            index.replacen(' ', "x", 4),
+        ] {
+            fs::write(index_path(&path), &damaged).unwrap();
+            assert_eq!(window(&path, Some(time(3)), None).unwrap(), None);
+        }
+
+        // a stale index that asked for no messages between checkpoints is
+        // rebuilt with one
+        fs::write(index_path(&path), index.replacen("every 2", "every 0", 1)).unwrap();
+        fs::write(&path, format!("{}{}", text, record(7).message("test"))).unwrap();
+        let found = window(&path, Some(time(7)), None).unwrap().unwrap();
+        assert_eq!(found.line, Some(6));
+        assert!(fs::read_to_string(index_path(&path))
+            .unwrap()
+            .contains("\nevery 1\n"));
+
+        // or not used if it can't be rebuilt
+        fs::write(&path, zstd::encode_all(text.as_bytes(), 0).unwrap()).unwrap();
+        assert_eq!(window(&path, Some(time(3)), None).unwrap(), None);
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use glob::Pattern;
+
+use crate::{
+    archive,
+    reader::{LogReader, RECORD_MARKER, STDIN_NAME},
+    LogError, LogStream,
+};
+
+/// The name given on the command line to read from std::in
+pub const STDIN_ARG: &str = "-";
+
+/// The bytes gzip compressed data starts with
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// The bytes zstd compressed data starts with
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// The bytes bzip2 compressed data starts with
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// Checks if the start of some data looks like it has been compressed
+pub(crate) fn is_compressed(header: &[u8]) -> bool {
+    [GZIP_MAGIC, ZSTD_MAGIC, BZIP2_MAGIC]
+        .iter()
+        .any(|magic| header.starts_with(magic))
+}
+
+/// Wraps a reader so that compressed data is decompressed as it is read.
+///
+/// The compression (gzip, zstd or bzip2) is detected from the first few bytes
+/// of the data rather than the name of the input, anything else is passed
+/// through unchanged.
+pub(crate) fn decompress<'a, R: BufRead + 'a>(
+    mut reader: R,
+    name: &str,
+) -> Result<Box<dyn BufRead + 'a>, LogError> {
+    let header = reader.fill_buf().map_err(|err| LogError {
+        cause: format!("Failed to read from {}: {}", name, err),
+    })?;
+
+    if header.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        )))
+    } else if header.starts_with(ZSTD_MAGIC) {
+        let decoder = zstd::stream::read::Decoder::with_buffer(reader).map_err(|err| LogError {
+            cause: format!("Unable to decompress {}: {}", name, err),
+        })?;
+        Ok(Box::new(BufReader::new(decoder)))
+    } else if header.starts_with(BZIP2_MAGIC) {
+        Ok(Box::new(BufReader::new(
+            bzip2::bufread::MultiBzDecoder::new(reader),
+        )))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Opens a file on disk as a stream of log messages
+pub(crate) fn open(path: &str) -> Result<LogStream, LogError> {
+    let handle = fs::File::open(path).map_err(|err| LogError {
+        cause: format!("Unable to open {}: {}", path, err),
+    })?;
+    let reader = decompress(BufReader::new(handle), path)?;
+    Ok(Box::new(LogReader::new(reader, path)))
+}
+
+/// Opens std::in as a stream of log messages
+pub(crate) fn stdin() -> Result<LogStream, LogError> {
+    let reader = decompress(io::stdin().lock(), STDIN_NAME)?;
+    Ok(Box::new(LogReader::new(reader, STDIN_NAME)))
+}
+
+/// Decides which files found in a directory should be read
+pub(crate) struct PathFilter {
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+    fn matches(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(path)))
+            && !self.exclude.iter().any(|p| p.matches(path))
+    }
+}
+
+/// Checks if a file looks like a visibroker log (or an archive that may contain
+/// some) by looking for a message at the start of the file
+pub(crate) fn is_log(path: &str) -> Result<bool, LogError> {
+    if archive::is_archive(path)? {
+        return Ok(true);
+    }
+
+    let handle = fs::File::open(path).map_err(|err| LogError {
+        cause: format!("Unable to open {}: {}", path, err),
+    })?;
+    let mut reader = decompress(BufReader::new(handle), path)?;
+    let header = match reader.fill_buf() {
+        Ok(header) => header,
+        // e.g. a file that claims to be compressed but isn't
+        Err(_) => return Ok(false),
+    };
+
+    Ok(has_records(header))
+}
+
+/// Checks if the start of some data has a line starting a record
+pub(crate) fn has_records(header: &[u8]) -> bool {
+    let marker = RECORD_MARKER.as_bytes();
+    header.starts_with(marker)
+        || header
+            .windows(marker.len() + 1)
+            .any(|window| window[0] == b'\n' && &window[1..] == marker)
+}
+
+/// Adds a file to the files to read if it matches the filter and looks like a
+/// log, `relative` being the path the filter is matched against
+fn add_file(path: &Path, relative: &str, filter: &PathFilter, files: &mut Vec<String>) {
+    if !path.is_file() || !filter.matches(relative) {
+        return;
+    }
+    let name = path.to_string_lossy().to_string();
+    // one file that can't be read shouldn't stop the rest being searched
+    match is_log(&name) {
+        Ok(true) => files.push(name),
+        Ok(false) => {}
+        Err(err) => eprintln!("WARNING: {}, skipping it", err.cause),
+    }
+}
+
+/// Searches a directory for logs, `visited` holds the directories already
+/// searched so a symlink back up the tree is not followed round forever
+fn walk(
+    dir: &Path,
+    root: &Path,
+    filter: &PathFilter,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<String>,
+) -> Result<(), LogError> {
+    if !visited.insert(fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())) {
+        return Ok(());
+    }
+
+    let entries = fs::read_dir(dir).map_err(|err| LogError {
+        cause: format!("Unable to read directory {}: {}", dir.display(), err),
+    })?;
+
+    let mut paths: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            // like a file, one directory that can't be read is skipped
+            if let Err(err) = walk(&path, root, filter, visited, files) {
+                eprintln!("WARNING: {}, skipping it", err.cause);
+            }
+            continue;
+        }
+
+        let relative = path.strip_prefix(root).unwrap_or(&path).to_string_lossy();
+        add_file(&path, &relative, filter, files);
+    }
+    Ok(())
+}
+
+/// The directory a pattern such as `logs/*/*.log` searches, the part before
+/// the first component with a wildcard in it
+fn pattern_root(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|part| !part.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Finds the files to read for a path given on the command line.
+///
+/// Directories are searched recursively for anything that looks like a log and
+/// matches the filter, and patterns such as `logs/*.log` are expanded in the
+/// same way with the filter matched against the path under `logs`. Anything
+/// else is returned as is.
+pub(crate) fn expand(path: &str, filter: &PathFilter) -> Result<Vec<String>, LogError> {
+    let mut files = Vec::new();
+
+    if Path::new(path).is_dir() {
+        walk(
+            Path::new(path),
+            Path::new(path),
+            filter,
+            &mut HashSet::new(),
+            &mut files,
+        )?;
+    } else if !Path::new(path).exists() && path.contains(['*', '?', '[']) {
+        let matches = glob::glob(path).map_err(|err| LogError {
+            cause: format!("Invalid file pattern {}: {}", path, err),
+        })?;
+        let root = pattern_root(path);
+        for found in matches.flatten() {
+            if found.is_dir() {
+                if let Err(err) = walk(&found, &found, filter, &mut HashSet::new(), &mut files) {
+                    eprintln!("WARNING: {}, skipping it", err.cause);
+                }
+            } else {
+                let relative = found
+                    .strip_prefix(&root)
+                    .unwrap_or(&found)
+                    .to_string_lossy();
+                add_file(&found, &relative, filter, &mut files);
+            }
+        }
+    } else {
+        files.push(path.to_string());
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{record, TempDir};
+    use std::io::{Read, Write};
+
+    #[test]
+    fn decompress_formats() {
+        let text = "Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test\n";
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(text.as_bytes()).unwrap();
+
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(text.as_bytes()).unwrap();
+
+        let cases = vec![
+            ("plain", text.as_bytes().to_vec()),
+            ("gzip", gzip.finish().unwrap()),
+            ("zstd", zstd::encode_all(text.as_bytes(), 0).unwrap()),
+            ("bzip2", bzip2.finish().unwrap()),
+        ];
+
+        for (name, data) in cases {
+            let mut output = String::new();
+            decompress(std::io::Cursor::new(data), name)
+                .unwrap()
+                .read_to_string(&mut output)
+                .unwrap();
+            assert_eq!(output, text, "Checking {} input is decompressed", name);
+        }
+    }
+
+    #[test]
+    fn expand_directory() {
+        let dir = TempDir::new("expand");
+        fs::create_dir_all(dir.join("server")).unwrap();
+
+        let record = record(0).message("test");
+        fs::write(dir.join("client.log"), &record).unwrap();
+        fs::write(dir.join("server/server.log"), &record).unwrap();
+        fs::write(dir.join("server/server.old"), &record).unwrap();
+        fs::write(dir.join("server/config.xml"), "<orb/>").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("server/loop")).unwrap();
+
+        let root = dir.to_string_lossy().to_string();
+        let names = |filter: &PathFilter| -> Vec<String> {
+            expand(&root, filter)
+                .unwrap()
+                .iter()
+                .map(|file| file[root.len() + 1..].to_string())
+                .collect()
+        };
+
+        let everything = PathFilter {
+            include: vec![],
+            exclude: vec![],
+        };
+        assert_eq!(
+            names(&everything),
+            vec!["client.log", "server/server.log", "server/server.old"]
+        );
+
+        let filtered = PathFilter {
+            include: vec![Pattern::new("server/*").unwrap()],
+            exclude: vec![Pattern::new("*.old").unwrap()],
+        };
+        assert_eq!(names(&filtered), vec!["server/server.log"]);
+
+        // files matching a pattern are filtered and checked in the same way
+        let pattern = format!("{}/server/server.*", root);
+        assert_eq!(
+            expand(&pattern, &everything).unwrap(),
+            vec![
+                format!("{}/server/server.log", root),
+                format!("{}/server/server.old", root)
+            ]
+        );
+        let old = PathFilter {
+            include: vec![],
+            exclude: vec![Pattern::new("*.old").unwrap()],
+        };
+        assert_eq!(
+            expand(&pattern, &old).unwrap(),
+            vec![format!("{}/server/server.log", root)]
+        );
+        let pattern = format!("{}/s*/server.*", root);
+        assert_eq!(
+            expand(&pattern, &filtered).unwrap(),
+            vec![format!("{}/server/server.log", root)]
+        );
+
+        // a directory that can't be read is skipped rather than stopping the search
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::create_dir_all(dir.join("locked")).unwrap();
+            fs::write(dir.join("locked/secret.log"), &record).unwrap();
+            fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+            let readable = fs::read_dir(dir.join("locked")).is_ok();
+            let found = names(&everything);
+            fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+            // root can read the directory anyway
+            if !readable {
+                assert_eq!(
+                    found,
+                    vec!["client.log", "server/server.log", "server/server.old"]
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,640 @@
+use std::fmt;
+
+use serde::Serialize;
+
+/// What a stringified IOR starts with
+const IOR_PREFIX: &str = "IOR:";
+
+const TAG_INTERNET_IOP: u32 = 0;
+const TAG_MULTIPLE_COMPONENTS: u32 = 1;
+
+const TAG_ORB_TYPE: u32 = 0;
+const TAG_CODE_SETS: u32 = 1;
+const TAG_POLICIES: u32 = 2;
+const TAG_ALTERNATE_IIOP_ADDRESS: u32 = 3;
+const TAG_SSL_SEC_TRANS: u32 = 20;
+const TAG_JAVA_CODEBASE: u32 = 25;
+const TAG_CSI_SEC_MECH_LIST: u32 = 33;
+const TAG_NULL_TAG: u32 = 34;
+const TAG_TLS_SEC_TRANS: u32 = 36;
+
+/// The names of the bits of the CSIv2 and SSL association options
+const ASSOCIATION_OPTIONS: [(u16, &str); 12] = [
+    (0x0001, "NoProtection"),
+    (0x0002, "Integrity"),
+    (0x0004, "Confidentiality"),
+    (0x0008, "DetectReplay"),
+    (0x0010, "DetectMisordering"),
+    (0x0020, "EstablishTrustInTarget"),
+    (0x0040, "EstablishTrustInClient"),
+    (0x0080, "NoDelegation"),
+    (0x0100, "SimpleDelegation"),
+    (0x0200, "CompositeDelegation"),
+    (0x0400, "IdentityAssertion"),
+    (0x0800, "DelegationByClient"),
+];
+
+/// The names of the code sets usually found in the code sets component
+const CODE_SETS: [(u32, &str); 5] = [
+    (0x0001_0001, "ISO-8859-1"),
+    (0x0001_0020, "ISO-646"),
+    (0x0001_0100, "UCS-2"),
+    (0x0001_0109, "UTF-16"),
+    (0x0501_0001, "UTF-8"),
+];
+
+/// A decoded object reference
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ior {
+    /// the repository id of the object's interface e.g. `IDL:Bank/Account:1.0`
+    pub type_id: String,
+    pub profiles: Vec<Profile>,
+}
+
+/// One of the ways of reaching the object
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "profile", rename_all = "snake_case")]
+pub enum Profile {
+    Iiop {
+        version: String,
+        host: String,
+        port: u16,
+        /// the object key with anything not printable escaped
+        object_key: String,
+        components: Vec<Component>,
+    },
+    MultipleComponents {
+        components: Vec<Component>,
+    },
+    Unknown {
+        tag: u32,
+        length: usize,
+    },
+}
+
+/// A tagged component of a profile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "component", rename_all = "snake_case")]
+pub enum Component {
+    OrbType {
+        orb_type: u32,
+    },
+    CodeSets {
+        char: String,
+        wchar: String,
+    },
+    Policies {
+        length: usize,
+    },
+    AlternateAddress {
+        host: String,
+        port: u16,
+    },
+    Ssl {
+        port: u16,
+        supports: Vec<&'static str>,
+        requires: Vec<&'static str>,
+    },
+    JavaCodebase {
+        codebase: String,
+    },
+    Csiv2 {
+        stateful: bool,
+        mechanisms: Vec<Mechanism>,
+    },
+    Unknown {
+        tag: u32,
+        length: usize,
+    },
+}
+
+/// One of the compound security mechanisms of a CSIv2 component
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mechanism {
+    pub requires: Vec<&'static str>,
+    pub transport: Transport,
+    /// what the client authentication layer supports and requires
+    pub authentication: (Vec<&'static str>, Vec<&'static str>),
+    /// what the identity assertion layer supports and requires
+    pub attributes: (Vec<&'static str>, Vec<&'static str>),
+}
+
+/// The transport layer of a CSIv2 mechanism
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "transport", rename_all = "snake_case")]
+pub enum Transport {
+    None,
+    Tls {
+        supports: Vec<&'static str>,
+        requires: Vec<&'static str>,
+        addresses: Vec<(String, u16)>,
+    },
+    Unknown {
+        tag: u32,
+    },
+}
+
+/// Reads the CDR encoded values of an encapsulation, the first octet says if
+/// it is little endian and everything is aligned from the start of it
+struct Cdr<'a> {
+    data: &'a [u8],
+    position: usize,
+    little_endian: bool,
+}
+
+impl<'a> Cdr<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, String> {
+        let mut cdr = Cdr {
+            data,
+            position: 0,
+            little_endian: false,
+        };
+        cdr.little_endian = match cdr.octet()? {
+            0 => false,
+            1 => true,
+            flag => return Err(format!("invalid byte order {}", flag)),
+        };
+        Ok(cdr)
+    }
+
+    fn take(&mut self, length: usize, alignment: usize) -> Result<&'a [u8], String> {
+        let start = self.position.next_multiple_of(alignment);
+        let end = start
+            .checked_add(length)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| format!("truncated at offset {}", start))?;
+        self.position = end;
+        Ok(&self.data[start..end])
+    }
+
+    fn octet(&mut self) -> Result<u8, String> {
+        Ok(self.take(1, 1)?[0])
+    }
+
+    fn boolean(&mut self) -> Result<bool, String> {
+        Ok(self.octet()? != 0)
+    }
+
+    fn ushort(&mut self) -> Result<u16, String> {
+        let bytes = self.take(2, 2)?.try_into().unwrap();
+        Ok(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn ulong(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4, 4)?.try_into().unwrap();
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn octets(&mut self) -> Result<&'a [u8], String> {
+        let length = self.ulong()? as usize;
+        self.take(length, 1)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let bytes = self.octets()?;
+        let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    /// Reads a sequence, the length is checked against what is left so a
+    /// corrupt length can not allocate a huge vector
+    fn sequence<T>(
+        &mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        let length = self.ulong()? as usize;
+        if length > self.data.len() - self.position {
+            return Err(format!("sequence of {} is longer than the data", length));
+        }
+        (0..length).map(|_| read(self)).collect()
+    }
+}
+
+/// The names of the association options set in a bit mask
+fn options(mask: u16) -> Vec<&'static str> {
+    ASSOCIATION_OPTIONS
+        .iter()
+        .filter(|(bit, _)| mask & bit != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+fn code_set(id: u32) -> String {
+    CODE_SETS
+        .iter()
+        .find(|(code_set, _)| *code_set == id)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| format!("{:#010x}", id))
+}
+
+/// Shows the printable ascii of an object key as it is and escapes the rest
+fn escape(key: &[u8]) -> String {
+    key.iter()
+        .map(|byte| match byte {
+            b'\\' => String::from("\\\\"),
+            b' '..=b'~' => (*byte as char).to_string(),
+            _ => format!("\\x{:02x}", byte),
+        })
+        .collect()
+}
+
+fn transport(tag: u32, data: &[u8]) -> Result<Transport, String> {
+    match tag {
+        TAG_NULL_TAG => Ok(Transport::None),
+        TAG_TLS_SEC_TRANS => {
+            let mut cdr = Cdr::new(data)?;
+            let supports = options(cdr.ushort()?);
+            let requires = options(cdr.ushort()?);
+            let addresses = cdr.sequence(|cdr| Ok((cdr.string()?, cdr.ushort()?)))?;
+            Ok(Transport::Tls {
+                supports,
+                requires,
+                addresses,
+            })
+        }
+        tag => Ok(Transport::Unknown { tag }),
+    }
+}
+
+fn mechanism(cdr: &mut Cdr) -> Result<Mechanism, String> {
+    let requires = options(cdr.ushort()?);
+    let tag = cdr.ulong()?;
+    let transport = transport(tag, cdr.octets()?)?;
+
+    let authentication = (options(cdr.ushort()?), options(cdr.ushort()?));
+    cdr.octets()?; // client authentication mechanism
+    cdr.octets()?; // target name
+
+    let attributes = (options(cdr.ushort()?), options(cdr.ushort()?));
+    cdr.sequence(|cdr| {
+        cdr.ulong()?;
+        cdr.octets()
+    })?; // privilege authorities
+    cdr.sequence(|cdr| cdr.octets())?; // naming mechanisms
+    cdr.ulong()?; // identity types
+
+    Ok(Mechanism {
+        requires,
+        transport,
+        authentication,
+        attributes,
+    })
+}
+
+fn decode_component(tag: u32, data: &[u8]) -> Result<Component, String> {
+    let mut cdr = Cdr::new(data)?;
+    let component = match tag {
+        TAG_ORB_TYPE => Component::OrbType {
+            orb_type: cdr.ulong()?,
+        },
+        TAG_CODE_SETS => {
+            let char = code_set(cdr.ulong()?);
+            cdr.sequence(|cdr| cdr.ulong())?;
+            let wchar = code_set(cdr.ulong()?);
+            Component::CodeSets { char, wchar }
+        }
+        TAG_POLICIES => Component::Policies { length: data.len() },
+        TAG_ALTERNATE_IIOP_ADDRESS => Component::AlternateAddress {
+            host: cdr.string()?,
+            port: cdr.ushort()?,
+        },
+        TAG_SSL_SEC_TRANS => {
+            let supports = options(cdr.ushort()?);
+            let requires = options(cdr.ushort()?);
+            Component::Ssl {
+                port: cdr.ushort()?,
+                supports,
+                requires,
+            }
+        }
+        TAG_JAVA_CODEBASE => Component::JavaCodebase {
+            codebase: cdr.string()?,
+        },
+        TAG_CSI_SEC_MECH_LIST => Component::Csiv2 {
+            stateful: cdr.boolean()?,
+            mechanisms: cdr.sequence(mechanism)?,
+        },
+        tag => Component::Unknown {
+            tag,
+            length: data.len(),
+        },
+    };
+    Ok(component)
+}
+
+/// Reads a list of tagged components, one that can not be decoded is kept as
+/// an unknown component rather than losing the rest of the IOR
+fn components(cdr: &mut Cdr) -> Result<Vec<Component>, String> {
+    cdr.sequence(|cdr| {
+        let tag = cdr.ulong()?;
+        let data = cdr.octets()?;
+        Ok(decode_component(tag, data).unwrap_or(Component::Unknown {
+            tag,
+            length: data.len(),
+        }))
+    })
+}
+
+fn profile(tag: u32, data: &[u8]) -> Result<Profile, String> {
+    match tag {
+        TAG_INTERNET_IOP => {
+            let mut cdr = Cdr::new(data)?;
+            let major = cdr.octet()?;
+            let minor = cdr.octet()?;
+            let host = cdr.string()?;
+            let port = cdr.ushort()?;
+            let object_key = escape(cdr.octets()?);
+            // IIOP 1.0 profiles have no components
+            let components = if minor > 0 {
+                components(&mut cdr)?
+            } else {
+                Vec::new()
+            };
+            Ok(Profile::Iiop {
+                version: format!("{}.{}", major, minor),
+                host,
+                port,
+                object_key,
+                components,
+            })
+        }
+        TAG_MULTIPLE_COMPONENTS => Ok(Profile::MultipleComponents {
+            components: components(&mut Cdr::new(data)?)?,
+        }),
+        tag => Ok(Profile::Unknown {
+            tag,
+            length: data.len(),
+        }),
+    }
+}
+
+/// Decodes a stringified IOR, with or without the `IOR:` prefix. It is the hex
+/// of a CDR encapsulation holding the type id of the object and its tagged
+/// profiles, the IIOP profile has the host, port and object key used to reach
+/// the object along with components such as the SSL and CSIv2 settings
+pub fn decode(ior: &str) -> Result<Ior, String> {
+    let hex = ior.strip_prefix(IOR_PREFIX).unwrap_or(ior);
+    if !hex.len().is_multiple_of(2) {
+        return Err(String::from("odd number of hex digits"));
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|index| {
+            hex.get(index..index + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("invalid hex at {}", index))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+
+    let mut cdr = Cdr::new(&bytes)?;
+    let type_id = cdr.string()?;
+    let profiles = cdr.sequence(|cdr| {
+        let tag = cdr.ulong()?;
+        profile(tag, cdr.octets()?)
+    })?;
+    Ok(Ior { type_id, profiles })
+}
+
+/// Finds the stringified IORs in a message
+pub fn find(message: &str) -> impl Iterator<Item = &str> {
+    message
+        .match_indices(IOR_PREFIX)
+        .filter(|(start, _)| {
+            // skip the end of a longer word e.g. `XIOR:`
+            !message[..*start].ends_with(|c: char| c.is_ascii_alphanumeric())
+        })
+        .map(|(start, _)| {
+            let hex = &message[start + IOR_PREFIX.len()..];
+            let end = hex
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(hex.len());
+            &message[start..start + IOR_PREFIX.len() + end]
+        })
+        .filter(|ior| ior.len() > IOR_PREFIX.len())
+}
+
+fn write_options(f: &mut fmt::Formatter, supports: &[&str], requires: &[&str]) -> fmt::Result {
+    write!(
+        f,
+        "supports {} requires {}",
+        if supports.is_empty() {
+            String::from("nothing")
+        } else {
+            supports.join(",")
+        },
+        if requires.is_empty() {
+            String::from("nothing")
+        } else {
+            requires.join(",")
+        }
+    )
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Component::OrbType { orb_type } => write!(f, "orb type {:#010x}", orb_type),
+            Component::CodeSets { char, wchar } => {
+                write!(f, "code sets char {} wchar {}", char, wchar)
+            }
+            Component::Policies { length } => write!(f, "policies ({} bytes)", length),
+            Component::AlternateAddress { host, port } => {
+                write!(f, "alternate address {}:{}", host, port)
+            }
+            Component::Ssl {
+                port,
+                supports,
+                requires,
+            } => {
+                write!(f, "ssl port {} ", port)?;
+                write_options(f, supports, requires)
+            }
+            Component::JavaCodebase { codebase } => write!(f, "java codebase {}", codebase),
+            Component::Csiv2 {
+                stateful,
+                mechanisms,
+            } => {
+                write!(
+                    f,
+                    "csiv2 {}",
+                    if *stateful { "stateful" } else { "stateless" }
+                )?;
+                for mechanism in mechanisms {
+                    write!(f, "\n    mechanism requires ")?;
+                    write!(f, "{}", mechanism.requires.join(","))?;
+                    match &mechanism.transport {
+                        Transport::None => write!(f, "\n      transport none")?,
+                        Transport::Tls {
+                            supports,
+                            requires,
+                            addresses,
+                        } => {
+                            let addresses: Vec<String> = addresses
+                                .iter()
+                                .map(|(host, port)| format!("{}:{}", host, port))
+                                .collect();
+                            write!(f, "\n      transport tls {} ", addresses.join(" "))?;
+                            write_options(f, supports, requires)?;
+                        }
+                        Transport::Unknown { tag } => write!(f, "\n      transport tag {}", tag)?,
+                    }
+                    write!(f, "\n      authentication ")?;
+                    write_options(f, &mechanism.authentication.0, &mechanism.authentication.1)?;
+                    write!(f, "\n      attributes ")?;
+                    write_options(f, &mechanism.attributes.0, &mechanism.attributes.1)?;
+                }
+                Ok(())
+            }
+            Component::Unknown { tag, length } => {
+                write!(f, "component tag {} ({} bytes)", tag, length)
+            }
+        }
+    }
+}
+
+/// Writes the IOR over several lines, each starting with two spaces so it can
+/// be shown under the message it came from
+impl fmt::Display for Ior {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "  type id {}", self.type_id)?;
+        for profile in &self.profiles {
+            let components = match profile {
+                Profile::Iiop {
+                    version,
+                    host,
+                    port,
+                    object_key,
+                    components,
+                } => {
+                    write!(f, "\n  iiop {} {}:{}", version, host, port)?;
+                    write!(f, "\n  object key {}", object_key)?;
+                    components
+                }
+                Profile::MultipleComponents { components } => {
+                    write!(f, "\n  multiple components")?;
+                    components
+                }
+                Profile::Unknown { tag, length } => {
+                    write!(f, "\n  profile tag {} ({} bytes)", tag, length)?;
+                    continue;
+                }
+            };
+            for component in components {
+                write!(f, "\n  {}", component)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BIG_ENDIAN: &str = "IOR:000000000000001549444c3a42616e6b2f4163636f756e743a312e30000000000000000200000000000000e8000102000000001162616e6b2e6578616d706c652e636f6d000036b00000001f00504d43000000040000000c2f62616e6b5f706f6100006163636f756e74310000000004000000000000000800000000564200000000000100000018000000000001000100000001050100010001010900000000000000140000000800000066000636b1000000210000005c00000000000000010066000000000024000000240000006600660000000000010000001162616e6b2e6578616d706c652e636f6d000036b20040004000000008060667810201010100000000040000000000000000000000000000000000006300000003000102";
+    const LITTLE_ENDIAN: &str = "IOR:010000001500000049444c3a42616e6b2f4163636f756e743a312e30000000000200000000000000e8000000010102001100000062616e6b2e6578616d706c652e636f6d0000b0361f00000000504d43000000040000000c2f62616e6b5f706f6100006163636f756e743100040000000000000008000000010000000000425601000000180000000100000001000100010000000100010509010100000000001400000008000000010066000600b136210000005c00000001000000010000006600000024000000240000000100660066000000010000001100000062616e6b2e6578616d706c652e636f6d0000b2364000400008000000060667810201010100000000000400000000000000000000000000006300000003000000000102";
+
+    #[test]
+    fn decode_iors() {
+        let ior = decode(BIG_ENDIAN).unwrap();
+        assert_eq!(decode(LITTLE_ENDIAN).unwrap(), ior);
+        assert_eq!(ior.type_id, "IDL:Bank/Account:1.0");
+        assert_eq!(ior.profiles.len(), 2);
+        assert_eq!(ior.profiles[1], Profile::Unknown { tag: 99, length: 3 });
+
+        let Profile::Iiop {
+            version,
+            host,
+            port,
+            object_key,
+            components,
+        } = &ior.profiles[0]
+        else {
+            panic!("not an iiop profile: {:?}", ior.profiles[0]);
+        };
+        assert_eq!(version, "1.2");
+        assert_eq!(host, "bank.example.com");
+        assert_eq!(*port, 14000);
+        assert_eq!(
+            object_key,
+            "\\x00PMC\\x00\\x00\\x00\\x04\\x00\\x00\\x00\\x0c/bank_poa\\x00\\x00account1"
+        );
+        assert_eq!(
+            components[..3],
+            [
+                Component::OrbType {
+                    orb_type: 0x5642_0000
+                },
+                Component::CodeSets {
+                    char: String::from("ISO-8859-1"),
+                    wchar: String::from("UTF-16")
+                },
+                Component::Ssl {
+                    port: 14001,
+                    supports: vec![
+                        "Integrity",
+                        "Confidentiality",
+                        "EstablishTrustInTarget",
+                        "EstablishTrustInClient"
+                    ],
+                    requires: vec!["Integrity", "Confidentiality"],
+                },
+            ]
+        );
+        let Component::Csiv2 {
+            stateful,
+            mechanisms,
+        } = &components[3]
+        else {
+            panic!("not a csiv2 component: {:?}", components[3]);
+        };
+        assert!(!stateful);
+        assert_eq!(
+            mechanisms[0].transport,
+            Transport::Tls {
+                supports: vec![
+                    "Integrity",
+                    "Confidentiality",
+                    "EstablishTrustInTarget",
+                    "EstablishTrustInClient"
+                ],
+                requires: vec![
+                    "Integrity",
+                    "Confidentiality",
+                    "EstablishTrustInTarget",
+                    "EstablishTrustInClient"
+                ],
+                addresses: vec![(String::from("bank.example.com"), 14002)],
+            }
+        );
+        assert_eq!(
+            mechanisms[0].authentication.1,
+            vec!["EstablishTrustInClient"]
+        );
+        assert_eq!(mechanisms[0].attributes.0, vec!["IdentityAssertion"]);
+
+        assert!(decode("IOR:0").is_err());
+        assert!(decode("IOR:zz").is_err());
+        assert!(decode(&BIG_ENDIAN[..100]).is_err());
+    }
+
+    #[test]
+    fn find_iors() {
+        let message = format!(
+            "bound {} and IOR:00ff, not XIOR:00 or IOR: or IOR:",
+            BIG_ENDIAN
+        );
+        assert_eq!(
+            find(&message).collect::<Vec<_>>(),
+            vec![BIG_ENDIAN, "IOR:00ff"]
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+};
+
+use chrono::TimeDelta;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+use crate::{stats::format_span, Log, LogError};
+
+/// How many of the starts that never ended are listed for each rule
+const UNMATCHED_SHOWN: usize = 10;
+
+/// Which messages the start and end of a pair have to come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Scope {
+    /// the same thread of the same process
+    #[default]
+    Thread,
+    /// the same process
+    Process,
+    /// anywhere
+    Global,
+}
+
+/// A rule as written in the rules file
+#[derive(Debug, Deserialize)]
+struct RuleDefinition {
+    name: String,
+    start: String,
+    end: String,
+    #[serde(default)]
+    scope: Scope,
+}
+
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleDefinition>,
+}
+
+/// Pairs the message that starts something with the message that ends it,
+/// the two are matched up using the named captures both patterns have
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    start: Regex,
+    end: Regex,
+    scope: Scope,
+    /// the names of the captures used to match a start with its end
+    keys: Vec<String>,
+}
+
+fn regex(path: &str, rule: &str, pattern: &str) -> Result<Regex, LogError> {
+    Regex::new(pattern).map_err(|err| LogError {
+        cause: format!("Invalid pattern in rule {} in {}: {}", rule, path, err),
+    })
+}
+
+impl Rule {
+    fn new(path: &str, definition: RuleDefinition) -> Result<Self, LogError> {
+        let start = regex(path, &definition.name, &definition.start)?;
+        let end = regex(path, &definition.name, &definition.end)?;
+        let end_names: Vec<&str> = end.capture_names().flatten().collect();
+        let mut keys: Vec<String> = start
+            .capture_names()
+            .flatten()
+            .filter(|name| end_names.contains(name))
+            .map(str::to_string)
+            .collect();
+        keys.sort();
+
+        Ok(Rule {
+            name: definition.name,
+            start,
+            end,
+            scope: definition.scope,
+            keys,
+        })
+    }
+
+    /// The key that matches a start up with its end
+    fn key(&self, log: &Log, captures: &Captures) -> String {
+        let mut key = match self.scope {
+            Scope::Thread => format!("pid={} tid={}", log.pid, log.tid),
+            Scope::Process => format!("pid={}", log.pid),
+            Scope::Global => String::new(),
+        };
+        for name in &self.keys {
+            if !key.is_empty() {
+                key.push(' ');
+            }
+            let value = captures.name(name).map(|m| m.as_str()).unwrap_or_default();
+            key.push_str(&format!("{}={}", name, value));
+        }
+        key
+    }
+}
+
+/// Reads the rules from a toml file, each rule is a `[[rule]]` table with a
+/// `name`, a `start` and `end` pattern and optionally a `scope` of `thread`
+/// (the default), `process` or `global`
+fn load(path: &str) -> Result<Vec<Rule>, LogError> {
+    let text = fs::read_to_string(path).map_err(|err| LogError {
+        cause: format!("Unable to read rules {}: {}", path, err),
+    })?;
+    let file: RulesFile = toml::from_str(&text).map_err(|err| LogError {
+        cause: format!("Unable to parse rules {}: {}", path, err),
+    })?;
+    if file.rule.is_empty() {
+        return Err(LogError {
+            cause: format!("No rules found in {}", path),
+        });
+    }
+
+    file.rule
+        .into_iter()
+        .map(|definition| Rule::new(path, definition))
+        .collect()
+}
+
+/// A start and the end it was paired with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Pair {
+    key: String,
+    start: Log,
+    end: Log,
+}
+
+impl Pair {
+    fn duration(&self) -> TimeDelta {
+        self.end.time - self.start.time
+    }
+}
+
+/// What has been found for a rule so far
+#[derive(Debug, Default)]
+struct Timings {
+    pairs: Vec<Pair>,
+    /// the starts still waiting for an end, oldest first
+    open: HashMap<String, VecDeque<Log>>,
+    unmatched_ends: usize,
+}
+
+/// Times how long things take by pairing up the messages that start and end them
+pub(crate) struct Latency {
+    rules: Vec<Rule>,
+    timings: Vec<Timings>,
+}
+
+impl Latency {
+    pub fn new(path: &str) -> Result<Self, LogError> {
+        let rules = load(path)?;
+        let timings = rules.iter().map(|_| Timings::default()).collect();
+        Ok(Latency { rules, timings })
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        for (rule, timings) in self.rules.iter().zip(&mut self.timings) {
+            if let Some(captures) = rule.end.captures(&log.message) {
+                let key = rule.key(log, &captures);
+                match timings.open.get_mut(&key).and_then(VecDeque::pop_front) {
+                    Some(start) => timings.pairs.push(Pair {
+                        key,
+                        start,
+                        end: log.clone(),
+                    }),
+                    None => timings.unmatched_ends += 1,
+                }
+            } else if let Some(captures) = rule.start.captures(&log.message) {
+                let key = rule.key(log, &captures);
+                timings.open.entry(key).or_default().push_back(log.clone());
+            }
+        }
+    }
+
+    /// Prints the percentiles of how long each rule took, the starts that
+    /// never ended and if `pairs` is set every pair that was found
+    pub fn print(&self, pairs: bool, date_fmt: &str) {
+        for (index, (rule, timings)) in self.rules.iter().zip(&self.timings).enumerate() {
+            if index > 0 {
+                println!();
+            }
+
+            let mut unmatched: Vec<(&String, &Log)> = timings
+                .open
+                .iter()
+                .flat_map(|(key, starts)| starts.iter().map(move |start| (key, start)))
+                .collect();
+            unmatched.sort_by_key(|(_, start)| start.time);
+
+            println!(
+                "{}: {} pairs, {} starts that never ended, {} ends without a start",
+                rule.name,
+                timings.pairs.len(),
+                unmatched.len(),
+                timings.unmatched_ends
+            );
+
+            let mut durations: Vec<TimeDelta> = timings.pairs.iter().map(Pair::duration).collect();
+            durations.sort();
+            if !durations.is_empty() {
+                println!(
+                    "  p50 {}  p95 {}  p99 {}  max {}",
+                    format_span(percentile(&durations, 50.0)),
+                    format_span(percentile(&durations, 95.0)),
+                    format_span(percentile(&durations, 99.0)),
+                    format_span(durations[durations.len() - 1])
+                );
+            }
+
+            if pairs {
+                for pair in &timings.pairs {
+                    println!(
+                        "  {:>12}  {} {} - {}  {}",
+                        format_span(pair.duration()),
+                        pair.start.time.format(date_fmt),
+                        pair.start.source,
+                        pair.end.source,
+                        pair.key
+                    );
+                }
+            }
+
+            for (key, start) in unmatched.iter().take(UNMATCHED_SHOWN) {
+                println!(
+                    "  never ended: {} {}  {}",
+                    start.time.format(date_fmt),
+                    start.source,
+                    key
+                );
+            }
+            if unmatched.len() > UNMATCHED_SHOWN {
+                println!("  ... and {} more", unmatched.len() - UNMATCHED_SHOWN);
+            }
+        }
+    }
+}
+
+/// The duration that the given percentage of the sorted durations are at or below
+fn percentile(durations: &[TimeDelta], percent: f64) -> TimeDelta {
+    let rank = (percent / 100.0 * durations.len() as f64).ceil() as usize;
+    durations[rank.clamp(1, durations.len()) - 1]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{logs, TempDir};
+
+    #[test]
+    fn pair_starts_and_ends() {
+        let dir = TempDir::new("latency");
+        let rules = dir.join("rules.toml");
+        fs::write(
+            &rules,
+            r#"
+[[rule]]
+name = "request"
+start = 'send (?P<op>\w+) id=(?P<id>\d+)'
+end = 'reply (?P<op>\w+) id=(?P<id>\d+)'
+"#,
+        )
+        .unwrap();
+
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:00 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# send ping id=1
+Pid# 1 Tim# Tue Jul  9 09:09:00 2024 100000us Tid# 2 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# send ping id=1
+Pid# 1 Tim# Tue Jul  9 09:09:01 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 2 Lvl# INFO Msg# reply ping id=1
+Pid# 1 Tim# Tue Jul  9 09:09:02 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# send ping id=2
+Pid# 1 Tim# Tue Jul  9 09:09:04 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 2 Lvl# INFO Msg# reply ping id=2
+Pid# 1 Tim# Tue Jul  9 09:09:05 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 2 Lvl# INFO Msg# reply ping id=3
+";
+        let mut latency = Latency::new(&rules.to_string_lossy()).unwrap();
+        for log in logs(text) {
+            latency.add(&log);
+        }
+
+        let timings = &latency.timings[0];
+        let pairs: Vec<(String, i64)> = timings
+            .pairs
+            .iter()
+            .map(|pair| (pair.key.clone(), pair.duration().num_milliseconds()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (String::from("pid=1 tid=1 id=1 op=ping"), 1000),
+                (String::from("pid=1 tid=1 id=2 op=ping"), 2000),
+            ]
+        );
+        assert_eq!(timings.open["pid=1 tid=2 id=1 op=ping"].len(), 1);
+        assert_eq!(timings.unmatched_ends, 1);
+
+        let durations: Vec<TimeDelta> = (1..=100).map(TimeDelta::milliseconds).collect();
+        assert_eq!(percentile(&durations, 50.0), TimeDelta::milliseconds(50));
+        assert_eq!(percentile(&durations, 99.0), TimeDelta::milliseconds(99));
+        assert_eq!(
+            percentile(&durations[..1], 95.0),
+            TimeDelta::milliseconds(1)
+        );
+    }
+}
@@ -0,0 +1,3 @@
+//! The parts of vislog that are useful outside of the command line tool
+
+pub mod ior;
@@ -1,51 +1,285 @@
-use chrono::NaiveDateTime;
-use clap::Parser;
+mod archive;
+mod collapse;
+mod connections;
+mod corba;
+mod diagnose;
+mod extract;
+mod first_error;
+mod follow;
+mod gaps;
+mod histogram;
+mod index;
+mod input;
+mod latency;
+mod merge;
+mod parallel;
+mod patterns;
+mod poa;
+mod processes;
+mod reader;
+mod rotation;
+mod seek;
+mod stats;
+#[cfg(test)]
+mod test_support;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use clap::{Parser, Subcommand};
+use collapse::{Collapse, Repeated};
+use connections::Connections;
+use corba::{ExceptionSummary, Exceptions};
+use diagnose::Diagnose;
+use extract::{Extractor, KV_PREFIX};
+use first_error::FirstError;
+use follow::{Follow, FollowedLog};
+use gaps::{Gaps, Per};
+use glob::Pattern;
+use histogram::{parse_bucket_size, BucketSize, Chart, Histogram};
+use index::Window;
+use input::PathFilter;
+use latency::Latency;
+use merge::MergedLogs;
+use patterns::{Miner, Patterns};
+use poa::Poas;
+use processes::Processes;
 use regex::{self, Regex};
-use std::{collections::HashMap,str, fs, io::{stdin, Read}, process::exit, usize};
+use rotation::RotatedLogs;
+use serde_json::json;
+use stats::{parse_duration, Stats};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    process::exit,
+    time::{Duration, Instant},
+};
+use vislog::ior::{self, Ior};
 
 /// A programe for parsing visibroker default log format
 #[derive(Parser)]
 #[command(version, about)]
+#[allow(clippy::upper_case_acronyms)]
 struct CLI {
     /// Process ID you want to search for
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     pid: Option<usize>,
     /// Thread ID you want to search for
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     tid: Option<usize>,
     /// The name of the logger you want to search for
-    #[arg(long)]
+    #[arg(long, global = true)]
     logger: Option<String>,
     /// The time which you want to see all log messages that apear before
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     before: Option<String>,
     /// The time which you want to see all log messages that apear before
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     after: Option<String>,
     /// The file which you want to see log messages from optionaly you can add a ':' and a line number which you want to see
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     source: Option<String>,
     /// The name of the component the message orignated from
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     component: Option<String>,
     /// The level of the messages you want to filter for
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     level: Option<String>,
     /// Regex that you want to grep the message for
-    #[arg(long, short)]
+    #[arg(long, short, global = true)]
     message: Option<String>,
     ///format of the output of the programe
-    #[arg(long,short,default_value_t=String::from("{level}: {message}"))]
+    #[arg(long,short,default_value_t=String::from("{level}: {message}"), global = true)]
     fmt: String,
 
     ///the format of the date and time string you want to use
-    #[arg(long,default_value_t=String::from("%a %b %e %H:%M:%S %Y %fus"))]
+    #[arg(long,default_value_t=String::from("%a %b %e %H:%M:%S %Y %fus"), global = true)]
     date_fmt: String,
-    /// The file you want to read
+    /// Prefix each message with the input file and line number it was read from
+    #[arg(long = "with-filename", short = 'H', global = true)]
+    with_filename: bool,
+    /// Print each message as a json object instead of using the output format
+    #[arg(long, global = true)]
+    json: bool,
+    /// Keep reading the files as they are written to, in the same way as `tail -F`
+    #[arg(long, short = 'F', conflicts_with_all = ["merge", "rotated"], global = true)]
+    follow: bool,
+    /// How long in milliseconds to wait for more of a message to be written before printing it when following a file
+    #[arg(long, default_value_t = 500, global = true)]
+    flush_timeout: u64,
+    /// Treat each file as the base name of a rotated log set (or a directory of them) and read each set as one stream
+    #[arg(long, global = true)]
+    rotated: bool,
+    /// Only read the members of an archive whose path matches this pattern e.g. '*.log', can be given more than once
+    #[arg(long, global = true)]
+    member: Vec<String>,
+    /// Interleave the messages of all the files in time order, each message is prefixed with the file it came from
+    #[arg(long, global = true)]
+    merge: bool,
+    /// When reading a directory only read the files whose path matches this pattern, can be given more than once
+    #[arg(long, global = true)]
+    include: Vec<String>,
+    /// When reading a directory skip the files whose path matches this pattern, can be given more than once
+    #[arg(long, global = true)]
+    exclude: Vec<String>,
+    /// The number of threads used to read large files, defaults to the number of cores
+    #[arg(long, short, value_parser = parallel::parse_jobs, global = true)]
+    jobs: Option<usize>,
+    /// Regex with named captures e.g. 'op=(?P<op>\w+)' run against each message, the captures become extra fields that can be used in the output format, json and csv output and --field, can be given more than once
+    #[arg(long, global = true)]
+    extract: Vec<String>,
+    /// Turn any key=value or key: value pairs in the messages into extra fields named kv.<key> e.g. {kv.host}
+    #[arg(long, global = true)]
+    kv: bool,
+    /// Look for CORBA system exceptions in the messages, adding the exception, minor, minor_reason and completed fields
+    #[arg(long, global = true)]
+    exceptions: bool,
+    /// A toml file of extra minor codes to decode, used in place of any built in ones with the same code, each is a [[minor]] with a code, a reason and optionally the exception it is for, implies --exceptions
+    #[arg(long, global = true)]
+    minor_codes: Option<String>,
+    /// Only show the messages with an extra field matching a regex, given as name=regex e.g. op=^get, can be given more than once
+    #[arg(long, value_parser = extract::parse_field, global = true)]
+    field: Vec<String>,
+    /// Print the messages as comma separated values with a header instead of using the output format
+    #[arg(long, conflicts_with = "json", global = true)]
+    csv: bool,
+    /// Decode the stringified object references (IOR:...) in the messages, printing the type id, IIOP version, host, port, object key and components such as SSL and CSIv2 under each message or adding them to the json output
+    #[arg(long, conflicts_with = "csv", global = true)]
+    decode_ior: bool,
+    /// Fold runs of consecutive messages with the same component, file, line, level and message into one with a repeat count
+    #[arg(long, global = true)]
+    collapse: bool,
+    /// When collapsing messages treat messages that only differ in their numbers and hex ids as the same
+    #[arg(long, requires = "collapse", global = true)]
+    ignore_numbers: bool,
+    /// Only show the messages that belong to this message template (as numbered by the patterns command), can be given more than once
+    #[arg(long, global = true)]
+    pattern_id: Vec<usize>,
+    /// Hide the messages that belong to this message template (as numbered by the patterns command), can be given more than once
+    #[arg(long, global = true)]
+    exclude_pattern_id: Vec<usize>,
+    /// Print a histogram of the number of messages over time instead of the messages, each bucket covers this long e.g. 1s, 5m or auto
+    #[arg(long, value_parser = parse_bucket_size, global = true)]
+    histogram: Option<BucketSize>,
+    /// How the histogram is drawn
+    #[arg(long, value_enum, default_value_t = Chart::Bars, global = true)]
+    chart: Chart,
+    /// Split each bucket of the histogram up by the level of the messages
+    #[arg(long, global = true)]
+    by_level: bool,
+    /// The files, directories or file patterns you want to read, use - to read from std::in
     files: Vec<String>,
+    /// The `kv.*` placeholders in the output format, found once from --fmt
+    #[arg(skip)]
+    kv_placeholders: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Subcommand)]
+enum Command {
+    /// Write an index of the times of the messages in each file next to it (as <file>.idx) so --after and --before can skip straight to the messages they want
+    Index {
+        /// The number of messages between each entry in the index
+        #[arg(long, default_value_t = 1000, value_parser = index::parse_every)]
+        every: usize,
+        /// The files you want to index
+        files: Vec<String>,
+    },
+    /// Print a summary of the messages kept: how many there are, the time they span and the most common levels, components, loggers, pids, tids, source lines and messages
+    Stats {
+        /// The number of most common values to show for each field
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// The files you want to summarise
+        files: Vec<String>,
+    },
+    /// Group the messages kept into templates by replacing the parts that vary (numbers, hex ids, IORs and quoted names) with placeholders and print each template with how many messages it has, when they were seen and a few examples
+    Patterns {
+        /// The number of example messages to show for each template
+        #[arg(long, default_value_t = 3)]
+        examples: usize,
+        /// Only show this many of the most common templates
+        #[arg(long)]
+        top: Option<usize>,
+        /// The files you want to find the templates of
+        files: Vec<String>,
+    },
+    /// Find the periods of time where a process or thread logged nothing while others kept logging, e.g. a hung dispatcher thread or a deadlock, showing the last message before each gap and the first one after it
+    Gaps {
+        /// How long a process or thread has to log nothing for to be reported e.g. 500ms, 5s or 1m
+        #[arg(long, value_parser = parse_duration, default_value = "5s")]
+        threshold: TimeDelta,
+        /// Whether to look for gaps in each process, each thread or both
+        #[arg(long, value_enum, default_value_t = Per::Both)]
+        per: Per,
+        /// The files you want to find the gaps in
+        files: Vec<String>,
+    },
+    /// Time how long things take by pairing up the messages that start and end them using the rules in a toml file, printing the p50/p95/p99/max of each rule and the starts that never ended
+    Latency {
+        /// The toml file with the rules in, each is a [[rule]] with a name, a start and end regex whose shared named captures pair them up, and a scope of thread (the default), process or global
+        #[arg(long)]
+        rules: String,
+        /// Also print every start and end that were paired up with how long it took
+        #[arg(long)]
+        pairs: bool,
+        /// The files you want to time
+        files: Vec<String>,
+    },
+    /// Summarise the CORBA system exceptions in the messages kept, with how many times each exception, minor code and completion status was seen, what the minor code means and which processes saw it
+    Exceptions {
+        /// The files you want to summarise the exceptions of
+        files: Vec<String>,
+    },
+    /// Print the POA hierarchy of each process from the POA names, object ids and the object keys of IORs in the messages, with when each POA and object was first and last mentioned and how many times it was activated and deactivated
+    Poa {
+        /// The files you want the POAs of
+        files: Vec<String>,
+    },
+    /// Track each process's connections through the messages that open, accept, close or reset them and print them with when they were opened and closed, how long they lasted and any errors
+    Connections {
+        /// Only show the connections that were never closed
+        #[arg(long)]
+        open: bool,
+        /// The files you want the connections of
+        files: Vec<String>,
+    },
+    /// List each process with its first and last record and how many records it logged at each level, spotting the startup and shutdown banners, restarts and processes that probably crashed
+    Processes {
+        /// How many of the last records of a process that probably crashed to print
+        #[arg(long, default_value_t = 10)]
+        last: usize,
+        /// A process that logged this close to the end of the logs without a shutdown message is taken as still running rather than crashed, e.g. 30s, 5m
+        #[arg(long, value_parser = parse_duration, default_value = "1m")]
+        running: TimeDelta,
+        /// The files you want the processes of
+        files: Vec<String>,
+    },
+    /// Match the messages against a file of signatures of known problems and report the ones found, most severe first, with what to do about them
+    Diagnose {
+        /// A toml (or yaml if named .yaml or .yml) file of signatures, each is a [[signature]] with a name, a severity (info, low, medium, high or critical), a remediation and a match table of regexes over the fields of a message, optionally an after table that has to match an earlier message of the same process, a count and a within duration
+        #[arg(long)]
+        signatures: String,
+        /// The files you want to diagnose
+        files: Vec<String>,
+    },
+    /// Print the first EMERG, ALERT, CRIT or ERROR record of each process, or the first record of its first burst of errors, with the records logged before it on the same thread
+    FirstError {
+        /// How many records from before the error on the same thread to print
+        #[arg(long, default_value_t = 10)]
+        context: usize,
+        /// How many errors it takes to make a burst, the first error of the first burst is printed
+        #[arg(long, default_value_t = 1)]
+        burst: usize,
+        /// The time the errors of a burst have to happen within e.g. 10s, 1m
+        #[arg(long, value_parser = parse_duration, default_value = "10s")]
+        within: TimeDelta,
+        /// The files you want the first errors of
+        files: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms)]
 enum LogLevel {
     EMERG,
     ALERT,
@@ -57,6 +291,17 @@ enum LogLevel {
 }
 
 impl LogLevel {
+    /// Every level from the most to the least severe
+    pub const ALL: [LogLevel; 7] = [
+        LogLevel::EMERG,
+        LogLevel::ALERT,
+        LogLevel::CRIT,
+        LogLevel::ERROR,
+        LogLevel::WARNING,
+        LogLevel::INFO,
+        LogLevel::DEBUG,
+    ];
+
     pub fn from(txt: String) -> Option<Self> {
         match txt.to_lowercase().as_str() {
             "emergency" | "emerg" | "emg" => Some(LogLevel::EMERG),
@@ -69,17 +314,20 @@ impl LogLevel {
             _ => None,
         }
     }
+}
 
-    pub fn to_string(self) -> String {
-        match self {
-            LogLevel::EMERG => "EMERG".to_string(),
-            LogLevel::ALERT => "ALERT".to_string(),
-            LogLevel::CRIT => "CRIT".to_string(),
-            LogLevel::ERROR => "ERROR".to_string(),
-            LogLevel::WARNING => "WARN".to_string(),
-            LogLevel::INFO => "INFO".to_string(),
-            LogLevel::DEBUG => "DEBUG".to_string(),
-        }
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::EMERG => "EMERG",
+            LogLevel::ALERT => "ALERT",
+            LogLevel::CRIT => "CRIT",
+            LogLevel::ERROR => "ERROR",
+            LogLevel::WARNING => "WARN",
+            LogLevel::INFO => "INFO",
+            LogLevel::DEBUG => "DEBUG",
+        };
+        write!(f, "{}", name)
     }
 }
 
@@ -88,7 +336,35 @@ struct LogError {
     cause: String,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Where abouts in the input a log message was read from
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Source {
+    /// The name of the file (or stream) the message was read from
+    input: String,
+    /// The line number in the input the message starts on, 0 if it is not
+    /// known
+    line: usize,
+    /// The byte offset in the input the message starts at
+    offset: usize,
+}
+
+impl Source {
+    /// The line number, or `?` if it is not known
+    fn line_name(&self) -> String {
+        match self.line {
+            0 => String::from("?"),
+            line => line.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.input, self.line_name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Log {
     pid: usize,
     time: NaiveDateTime,
@@ -99,9 +375,21 @@ struct Log {
     line: usize,
     level: LogLevel,
     message: String,
+    source: Source,
+    /// The id of the message template the message belongs to, only set when
+    /// patterns are being mined
+    pattern_id: Option<usize>,
+    /// Extra fields pulled out of the message e.g. by --extract
+    fields: BTreeMap<String, String>,
 }
 
 impl Log {
+    #[allow(
+        clippy::needless_late_init,
+        clippy::useless_conversion,
+        clippy::redundant_field_names,
+        clippy::needless_return
+    )]
     pub fn from(text: String) -> Result<Log, LogError> {
         let marker_size = 4;
 
@@ -294,14 +582,26 @@ impl Log {
             line: line_no,
             level: log_level,
             message: message_str.trim().to_string(),
+            source: Source::default(),
+            pattern_id: None,
+            fields: BTreeMap::new(),
         });
     }
+}
 
-    pub fn from_log(text: &String, start: usize, end: usize) -> Result<Log, LogError> {
-        let log_str: String = text.chars().skip(start).take(end - start).collect();
+type Filter = dyn Fn(&Log, &CLI) -> bool + Sync;
 
-        Log::from(log_str)
-    }
+/// A stream of log messages read from one or more inputs
+type LogStream = Box<dyn Iterator<Item = Result<Log, LogError>>>;
+
+/// An input along with how it should be read
+enum Input {
+    Stream(LogStream),
+    /// A large file that can be split up and read on several threads, if a
+    /// window is given only that part of the file needs to be read
+    Mapped(String, Option<Window>),
+    /// Files being followed as they are written to
+    Followed(Follow),
 }
 
 fn tid_validator(log: &Log, args: &CLI) -> bool {
@@ -341,164 +641,676 @@ fn file_validator(log: &Log, args: &CLI) -> bool {
     log.file == args.source.clone().unwrap()
 }
 
-fn print_log(log: Log, format: &String, date_fromat: &String) {
+fn print_log(log: Log, args: &CLI) {
+    let last = log.time;
+    print_repeated(
+        Repeated {
+            log,
+            count: 1,
+            last,
+        },
+        args,
+    )
+}
+
+/// The names of the `kv.*` placeholders in an output format
+fn kv_placeholders(fmt: &str) -> Vec<String> {
+    Regex::new(r"\{(kv\.[^{}:]+)")
+        .unwrap()
+        .captures_iter(fmt)
+        .map(|name| name[1].to_string())
+        .collect()
+}
+
+/// Prints a message that may have been repeated, if it was the number of times
+/// and the time of the last one is added to the end of it
+fn print_repeated(repeated: Repeated, args: &CLI) {
+    let Repeated { log, count, last } = repeated;
+    let time = log.time.format(&args.date_fmt).to_string();
+    let last = last.format(&args.date_fmt).to_string();
+    let iors: Vec<Result<Ior, String>> = if args.decode_ior {
+        ior::find(&log.message).map(ior::decode).collect()
+    } else {
+        Vec::new()
+    };
+
+    if args.json {
+        let mut object = json!({
+            "pid": log.pid,
+            "time": time,
+            "tid": log.tid,
+            "logger": log.logger,
+            "component": log.component,
+            "file": log.file,
+            "line": log.line,
+            "level": log.level.to_string(),
+            "message": log.message,
+            "input": log.source.input,
+            "input_line": (log.source.line > 0).then_some(log.source.line),
+            "offset": log.source.offset,
+        });
+        if let Some(id) = log.pattern_id {
+            object["pattern_id"] = json!(id);
+        }
+        if args.collapse {
+            object["count"] = json!(count);
+            object["last_time"] = json!(last);
+        }
+        for (name, value) in &log.fields {
+            if let Some(key) = name.strip_prefix(KV_PREFIX) {
+                if object.get("kv").is_none() {
+                    object["kv"] = json!({});
+                }
+                object["kv"][key] = json!(value);
+            } else if object.get(name).is_none() {
+                object[name] = json!(value);
+            }
+        }
+        if args.decode_ior {
+            object["iors"] = iors
+                .iter()
+                .map(|ior| match ior {
+                    Ok(ior) => json!(ior),
+                    Err(cause) => json!({ "error": cause }),
+                })
+                .collect();
+        }
+        println!("{}", object);
+        return;
+    }
+
+    if args.csv {
+        let input_line = log.source.line_name();
+        let mut row = vec![
+            log.pid.to_string(),
+            time,
+            log.tid.to_string(),
+            log.logger,
+            log.component,
+            log.file,
+            log.line.to_string(),
+            log.level.to_string(),
+            log.message,
+            log.source.input,
+            input_line,
+            log.source.offset.to_string(),
+        ];
+        if needs_patterns(args) {
+            row.push(log.pattern_id.map(|id| id.to_string()).unwrap_or_default());
+        }
+        if args.collapse {
+            row.push(count.to_string());
+            row.push(last);
+        }
+        // the key=value pairs differ between messages so they can not be columns
+        row.extend(
+            log.fields
+                .into_iter()
+                .filter(|(name, _)| !name.starts_with(KV_PREFIX))
+                .map(|(_, value)| value),
+        );
+        println!("{}", csv_row(&row));
+        return;
+    }
+
     let mut vars = HashMap::new();
     vars.insert("pid".to_string(), log.pid.to_string());
-    vars.insert("time".to_string(), log.time.format(&date_fromat).to_string());
+    vars.insert("time".to_string(), time);
     vars.insert("tid".to_string(), log.tid.to_string());
-    vars.insert("logger".to_string(), log.logger.to_string());
+    vars.insert("logger".to_string(), log.logger);
     vars.insert("component".to_string(), log.component);
     vars.insert("file".to_string(), log.file);
     vars.insert("line".to_string(), log.line.to_string());
     vars.insert("level".to_string(), log.level.to_string());
     vars.insert("message".to_string(), log.message);
+    vars.insert("input_line".to_string(), log.source.line_name());
+    vars.insert("input".to_string(), log.source.input);
+    vars.insert("offset".to_string(), log.source.offset.to_string());
+    vars.insert(
+        "pattern_id".to_string(),
+        log.pattern_id.map(|id| id.to_string()).unwrap_or_default(),
+    );
+    vars.insert("count".to_string(), count.to_string());
+    vars.insert("last_time".to_string(), last);
+    for (name, value) in log.fields {
+        vars.entry(name).or_insert(value);
+    }
+    // not every message has every key so the missing ones are left empty
+    for name in &args.kv_placeholders {
+        vars.entry(name.clone()).or_default();
+    }
+
+    let prefix = if args.with_filename || args.merge {
+        format!("{}:{}:", vars["input"], vars["input_line"])
+    } else {
+        String::new()
+    };
+
+    let suffix = if count > 1 && !args.fmt.contains("{count}") {
+        format!(
+            " [repeated {} times from {} to {}]",
+            count, vars["time"], vars["last_time"]
+        )
+    } else {
+        String::new()
+    };
 
     println!(
-        "{}",
-        strfmt::strfmt(format.as_str(), &vars).expect("Failed to format output")
-    )
+        "{}{}{}",
+        prefix,
+        strfmt::strfmt(args.fmt.as_str(), &vars).expect("Failed to format output"),
+        suffix
+    );
+    for ior in iors {
+        match ior {
+            Ok(ior) => println!("{}", ior),
+            Err(cause) => println!("  unable to decode IOR: {}", cause),
+        }
+    }
+}
+
+/// Joins the values of a row of comma separated values, quoting the ones that need it
+fn csv_row<S: AsRef<str>>(values: &[S]) -> String {
+    values
+        .iter()
+        .map(|value| {
+            let value = value.as_ref();
+            if value.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", value.replace('"', "\"\""))
+            } else {
+                value.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Prints the names of the columns of the csv output
+fn print_csv_header(args: &CLI, extractor: &Extractor) {
+    let mut header = vec![
+        "pid",
+        "time",
+        "tid",
+        "logger",
+        "component",
+        "file",
+        "line",
+        "level",
+        "message",
+        "input",
+        "input_line",
+        "offset",
+    ];
+    if needs_patterns(args) {
+        header.push("pattern_id");
+    }
+    if args.collapse {
+        header.extend(["count", "last_time"]);
+    }
+    let names = extractor.names();
+    header.extend(names.iter().map(String::as_str));
+    println!("{}", csv_row(&header));
+}
+
+fn keep(log: &Log, args: &CLI, filters: &[&Filter]) -> bool {
+    filters.iter().all(|filter| filter(log, args))
+}
+
+fn exit_on_error<T>(result: Result<T, LogError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("ERROR: {}", err.cause);
+            exit(1);
+        }
+    }
+}
+
+/// Checks if the messages need to be put into templates, i.e. they are being
+/// filtered by template or the template is part of the output
+fn needs_patterns(args: &CLI) -> bool {
+    !args.pattern_id.is_empty()
+        || !args.exclude_pattern_id.is_empty()
+        || args.fmt.contains("{pattern_id}")
+}
+
+fn pattern_validator(log: &Log, args: &CLI) -> bool {
+    let Some(id) = log.pattern_id else {
+        return true;
+    };
+    (args.pattern_id.is_empty() || args.pattern_id.contains(&id))
+        && !args.exclude_pattern_id.contains(&id)
+}
+
+/// Builds what pulls the extra fields out of the messages from the options given
+fn extractor(args: &CLI) -> Result<Extractor, LogError> {
+    let exceptions = match &args.minor_codes {
+        Some(path) => Some(Exceptions::new().with_minor_codes(path)?),
+        None => args.exceptions.then(Exceptions::new),
+    };
+    Extractor::new(&args.extract)?
+        .with_kv(args.kv)
+        .with_exceptions(exceptions)
+        .with_filters(&args.field)
+}
+
+/// Reads the messages from every input, passing the ones that are kept to `output`
+fn for_each_log<F>(
+    inputs: Vec<Input>,
+    args: &CLI,
+    extractor: &Extractor,
+    filters: &[&Filter],
+    mut output: F,
+) where
+    F: FnMut(Log),
+{
+    for_each_log_or_idle(inputs, args, extractor, filters, |log| {
+        if let Some(log) = log {
+            output(log)
+        }
+    });
+}
+
+/// Like `for_each_log` but when following files `output` is also given None
+/// each time there are no new messages to read
+fn for_each_log_or_idle<F>(
+    inputs: Vec<Input>,
+    args: &CLI,
+    extractor: &Extractor,
+    filters: &[&Filter],
+    output: F,
+) where
+    F: FnMut(Option<Log>),
+{
+    let mut miner = needs_patterns(args).then(Miner::default);
+    for_each_mined_log(inputs, args, extractor, filters, miner.as_mut(), output);
+}
+
+/// Reads the messages from every input, passing the ones that are kept to
+/// `output`, if a miner is given every message is put into a template first.
+/// When following files `output` is given None each time there are no new
+/// messages to read
+fn for_each_mined_log<F>(
+    inputs: Vec<Input>,
+    args: &CLI,
+    extractor: &Extractor,
+    filters: &[&Filter],
+    mut miner: Option<&mut Miner>,
+    mut output: F,
+) where
+    F: FnMut(Option<Log>),
+{
+    let streams: Vec<Input> = if args.merge {
+        let streams = exit_on_error(inputs.into_iter().map(Input::into_stream).collect());
+        vec![Input::Stream(Box::new(MergedLogs::new(streams)))]
+    } else if miner.is_some() {
+        // the parallel reader filters the messages before they are put back in
+        // order, so it can't be used when every message has to be mined in turn
+        exit_on_error(
+            inputs
+                .into_iter()
+                .map(|input| input.into_stream().map(Input::Stream))
+                .collect(),
+        )
+    } else {
+        inputs
+    };
+
+    // templates depend on the order the messages are seen in so they are found
+    // one message at a time, before the other filters so a template gets the
+    // same id whichever filters are used
+    let mut mine = |log: &mut Log| match miner.as_mut() {
+        Some(miner) => {
+            log.pattern_id = Some(miner.add(log));
+            pattern_validator(log, args)
+        }
+        None => true,
+    };
+    let keep = |log: &mut Log| {
+        extractor.extract(log);
+        extractor.matches(log) && keep(log, args, filters)
+    };
+
+    for input in streams {
+        match input {
+            Input::Stream(logs) => {
+                for log in logs {
+                    let mut log = exit_on_error(log);
+                    if mine(&mut log) && keep(&mut log) {
+                        output(Some(log));
+                    }
+                }
+            }
+            Input::Mapped(path, window) => {
+                let jobs = args.jobs.unwrap_or_else(|| {
+                    std::thread::available_parallelism()
+                        .map(|jobs| jobs.get())
+                        .unwrap_or(1)
+                });
+                exit_on_error(parallel::read(&path, window, jobs, keep, &mut |log| {
+                    output(Some(log))
+                }));
+            }
+            Input::Followed(mut follow) => loop {
+                match follow.poll() {
+                    Some(log) => {
+                        let mut log = exit_on_error(log);
+                        if mine(&mut log) && keep(&mut log) {
+                            output(Some(log));
+                        }
+                    }
+                    None => {
+                        output(None);
+                        follow.wait();
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl Input {
+    fn into_stream(self) -> Result<LogStream, LogError> {
+        match self {
+            Input::Stream(logs) => Ok(logs),
+            Input::Mapped(path, Some(window)) => index::open(&path, window),
+            Input::Mapped(path, None) => input::open(&path),
+            Input::Followed(follow) => Ok(Box::new(follow)),
+        }
+    }
+}
+
+fn patterns(values: &[String]) -> Result<Vec<Pattern>, LogError> {
+    values
+        .iter()
+        .map(|value| {
+            Pattern::new(value).map_err(|err| LogError {
+                cause: format!("Invalid pattern {}: {}", value, err),
+            })
+        })
+        .collect()
+}
+
+fn parse_time(value: &Option<String>, date_fmt: &str) -> Result<Option<NaiveDateTime>, LogError> {
+    value
+        .as_ref()
+        .map(|value| {
+            NaiveDateTime::parse_from_str(value, date_fmt).map_err(|err| LogError {
+                cause: format!("Unable to parse the time {}: {}", value, err),
+            })
+        })
+        .transpose()
 }
 
-fn filtered_print(log: Log, args: &CLI, filters: &Vec<&dyn Fn(&Log, &CLI) -> bool>) {
-    let mut filterd = false;
+/// Opens the files to read, `mine` is set if every message in them is needed to
+/// put the messages into templates so none of a file can be skipped
+fn open_inputs(args: &CLI, mine: bool) -> Result<Vec<Input>, LogError> {
+    let mut inputs = Vec::new();
+    if args.follow && !args.files.is_empty() {
+        let timeout = Duration::from_millis(args.flush_timeout);
+        let mut logs = Vec::new();
+        for file in &args.files {
+            if file == input::STDIN_ARG {
+                return Err(LogError {
+                    cause: String::from(
+                        "stdin (-) can not be followed, pipe into vislog without -F instead",
+                    ),
+                });
+            }
+            logs.push(FollowedLog::new(file, timeout)?);
+        }
+        inputs.push(Input::Followed(Follow::new(logs)));
+        return Ok(inputs);
+    }
 
-    for filter_index in 0..filters.len() {
-        if !(filters[filter_index])(&log, &args) {
-            filterd = true;
-            break;
+    let members = patterns(&args.member)?;
+    let after = parse_time(&args.after, &args.date_fmt)?;
+    let before = parse_time(&args.before, &args.date_fmt)?;
+    let filter = PathFilter {
+        include: patterns(&args.include)?,
+        exclude: patterns(&args.exclude)?,
+    };
+
+    for path in &args.files {
+        if args.rotated {
+            for set in rotation::discover(path)? {
+                inputs.push(Input::Stream(Box::new(RotatedLogs::new(set)?)));
+            }
+            continue;
+        }
+
+        for file in input::expand(path, &filter)? {
+            if file == input::STDIN_ARG {
+                inputs.push(Input::Stream(input::stdin()?));
+            } else if archive::is_archive(&file)? {
+                inputs.push(Input::Stream(archive::open(&file, &members)?));
+            } else {
+                let window = match (after, before) {
+                    (None, None) => None,
+                    _ if mine => None,
+                    _ => match index::window(&file, after, before)? {
+                        Some(window) => Some(window),
+                        None => seek::window(&file, after, before)?,
+                    },
+                };
+
+                if args.jobs != Some(1) && parallel::can_map(&file) {
+                    inputs.push(Input::Mapped(file, window));
+                } else if let Some(window) = window {
+                    inputs.push(Input::Stream(index::open(&file, window)?));
+                } else {
+                    inputs.push(Input::Stream(input::open(&file)?));
+                }
+            }
         }
     }
 
-    if !filterd {
-        print_log(log, &args.fmt,&args.date_fmt);
+    if args.files.is_empty() {
+        inputs.push(Input::Stream(input::stdin()?));
     }
+
+    Ok(inputs)
 }
 
 fn main() {
-    let args = CLI::parse();
+    let mut args = CLI::parse();
+    let command = args.command.take();
+    if args.kv {
+        args.kv_placeholders = kv_placeholders(&args.fmt);
+    }
+
+    match &command {
+        Some(Command::Index { every, files }) => {
+            for file in files {
+                exit_on_error(index::build(file, *every));
+            }
+            return;
+        }
+        Some(Command::Stats { files, .. })
+        | Some(Command::Patterns { files, .. })
+        | Some(Command::Gaps { files, .. })
+        | Some(Command::Latency { files, .. })
+        | Some(Command::Poa { files })
+        | Some(Command::Connections { files, .. })
+        | Some(Command::Processes { files, .. })
+        | Some(Command::Diagnose { files, .. })
+        | Some(Command::FirstError { files, .. }) => args.files.extend(files.iter().cloned()),
+        Some(Command::Exceptions { files }) => {
+            args.files.extend(files.iter().cloned());
+            args.exceptions = true;
+        }
+        None => {}
+    }
 
-    let mut filters: Vec<&dyn Fn(&Log, &CLI) -> bool> = Vec::new();
+    let mut filters: Vec<&Filter> = Vec::new();
 
-    if let Some(_) = args.tid {
+    if args.tid.is_some() {
         filters.push(&tid_validator);
     }
 
-    if let Some(_) = args.logger.clone() {
+    if args.logger.is_some() {
         filters.push(&logger_validator);
     }
 
-    if let Some(_) = args.component.clone() {
+    if args.component.is_some() {
         filters.push(&component_validator);
     }
 
-    if let Some(_) = args.level.clone() {
+    if args.level.is_some() {
         filters.push(&level_validator);
     }
 
-    if let Some(_) = args.message.clone() {
+    if args.message.is_some() {
         filters.push(&message_validator);
     }
 
-    if let Some(_) = args.before.clone() {
+    if args.before.is_some() {
         filters.push(&before_validator);
     }
 
-    if let Some(_) = args.after.clone() {
+    if args.after.is_some() {
         filters.push(&after_validator);
     }
 
-    if let Some(_) = args.source.clone() {
+    if args.source.is_some() {
         filters.push(&file_validator);
     }
 
-    let files = args.files.clone();
-    if files.len() != 0 {
-        for file in files {
-            let contents: String = fs::read_to_string(file).expect("No such file or Directory");
-            let pids: Vec<_> = contents.match_indices("Pid#").collect();
-
-            for index in 0..pids.len() - 1 {
-                let log;
-                match Log::from_log(&contents, pids[index].0, pids[index + 1].0) {
-                    Ok(data) => log = data,
-                    Err(err) => {
-                        eprintln!("ERROR: {}", err.cause);
-                        exit(1);
-                    }
-                }
-                filtered_print(log, &args, &filters);
-            }
-
-            let log;
+    let extractor = exit_on_error(extractor(&args));
 
-            match Log::from_log(&contents, pids[pids.len() - 1].0, contents.len()) {
-                Ok(data) => log = data,
-                Err(err) => {
-                    eprintln!("ERROR: {}", err.cause);
-                    exit(1);
-                }
-            }
+    let mine = needs_patterns(&args) || matches!(command, Some(Command::Patterns { .. }));
+    let inputs = exit_on_error(open_inputs(&args, mine));
 
-            filtered_print(log, &args, &filters);
+    match command {
+        Some(Command::Stats { top, .. }) => {
+            let mut stats = Stats::default();
+            for_each_log(inputs, &args, &extractor, &filters, |log| stats.add(&log));
+            stats.print(top, &args.date_fmt);
         }
-    } else {
-        const BUFFER_SIZE: usize = 1024;
-        let mut buffer: [u8;BUFFER_SIZE] = [0;BUFFER_SIZE];
-        let mut text: String = String::new();
-        loop {
-            let size;
-            match stdin().read(&mut buffer) {
-                Ok(buf_size) => size = buf_size ,
-                Err(_) => {
-                    eprintln!("ERROR: Failed to read from std::in");
-                    exit(1);
-                },
-            }
-
-            if size == 0 {
-                break;
-            }
-
-            text.push_str( match std::str::from_utf8(& buffer) {
-                Ok(txt) => txt,
-                Err(_) => {
-                    eprint!("ERROR: data from std::in is not utf-8 formatted");
-                    exit(1)
+        Some(Command::Patterns { examples, top, .. }) => {
+            let (mut miner, mut patterns) = (Miner::default(), Patterns::default());
+            for_each_mined_log(
+                inputs,
+                &args,
+                &extractor,
+                &filters,
+                Some(&mut miner),
+                |log| {
+                    if let Some(log) = log {
+                        patterns.add(log, examples)
+                    }
                 },
+            );
+            patterns.print(&miner, top, &args.date_fmt);
+        }
+        Some(Command::Gaps { threshold, per, .. }) => {
+            let mut gaps = Gaps::new(threshold, per);
+            for_each_log(inputs, &args, &extractor, &filters, |log| gaps.add(&log));
+            gaps::print(&gaps.finish(), &args.date_fmt);
+        }
+        Some(Command::Latency { rules, pairs, .. }) => {
+            let mut latency = exit_on_error(Latency::new(&rules));
+            for_each_log(inputs, &args, &extractor, &filters, |log| latency.add(&log));
+            latency.print(pairs, &args.date_fmt);
+        }
+        Some(Command::Exceptions { .. }) => {
+            let mut summary = ExceptionSummary::default();
+            for_each_log(inputs, &args, &extractor, &filters, |log| summary.add(&log));
+            summary.print(&args.date_fmt);
+        }
+        Some(Command::Poa { .. }) => {
+            let mut poas = Poas::new();
+            for_each_log(inputs, &args, &extractor, &filters, |log| poas.add(&log));
+            poas.print(&args.date_fmt);
+        }
+        Some(Command::Connections { open, .. }) => {
+            let mut connections = Connections::new();
+            for_each_log(inputs, &args, &extractor, &filters, |log| {
+                connections.add(&log)
             });
-
-            let pids: Vec<_> = text.match_indices("Pid#").collect();
-
-            for index in 0..pids.len() - 1 {
-                let log;
-                match Log::from_log(&text, pids[index].0, pids[index + 1].0) {
-                    Ok(data) => log = data,
-                    Err(err) => {
-                        eprintln!("ERROR: {}", err.cause);
-                        exit(1);
+            connections.print(open, &args.date_fmt);
+        }
+        Some(Command::Processes { last, running, .. }) => {
+            let mut processes = Processes::new(last, running);
+            for_each_log(inputs, &args, &extractor, &filters, |log| {
+                processes.add(&log)
+            });
+            processes.print(&args.date_fmt);
+        }
+        Some(Command::Diagnose { signatures, .. }) => {
+            let mut diagnose = exit_on_error(Diagnose::new(&signatures));
+            for_each_log(inputs, &args, &extractor, &filters, |log| {
+                diagnose.add(&log)
+            });
+            diagnose.print(&args.date_fmt);
+        }
+        Some(Command::FirstError {
+            context,
+            burst,
+            within,
+            ..
+        }) => {
+            let mut first_error = FirstError::new(context, burst, within);
+            for_each_log(inputs, &args, &extractor, &filters, |log| {
+                first_error.add(&log)
+            });
+            first_error.print(&args.date_fmt);
+        }
+        _ => match args.histogram {
+            Some(size) => {
+                let mut histogram = Histogram::new(size);
+                for_each_log(inputs, &args, &extractor, &filters, |log| {
+                    histogram.add(&log)
+                });
+                exit_on_error(histogram.print(args.chart, args.by_level, &args.date_fmt));
+            }
+            None if args.collapse => {
+                if args.csv {
+                    print_csv_header(&args, &extractor);
+                }
+                let mut collapse = Collapse::new(args.ignore_numbers);
+                // when following, a run is printed once nothing more has been
+                // added to it for a while rather than waiting for the next message
+                let timeout = Duration::from_millis(args.flush_timeout);
+                let mut last = Instant::now();
+                for_each_log_or_idle(inputs, &args, &extractor, &filters, |log| {
+                    let repeated = match log {
+                        Some(log) => {
+                            last = Instant::now();
+                            collapse.push(log)
+                        }
+                        None if last.elapsed() >= timeout => collapse.finish(),
+                        None => None,
+                    };
+                    if let Some(repeated) = repeated {
+                        print_repeated(repeated, &args);
                     }
+                });
+                if let Some(repeated) = collapse.finish() {
+                    print_repeated(repeated, &args);
                 }
-                filtered_print(log, &args, &filters);
             }
-
-            text = text.chars().skip(pids[pids.len()-1 ].0).take(text.len() - pids[pids.len() - 1].0).collect();
-        }
-        let log;
-        match Log::from_log(&text, 0, text.len()) {
-            Ok(data) => log = data,
-            Err(err) => {
-                eprintln!("ERROR: {}", err.cause);
-                exit(1);
+            None => {
+                if args.csv {
+                    print_csv_header(&args, &extractor);
+                }
+                for_each_log(inputs, &args, &extractor, &filters, |log| {
+                    print_log(log, &args)
+                })
             }
-        }
-        filtered_print(log, &args, &filters);
-
+        },
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_is_valid() {
+        CLI::command().debug_assert();
+    }
 
     #[test]
     fn log_level_from() {
@@ -555,116 +1367,146 @@ mod test {
     #[test]
     fn log_from() {
         let cases = vec![
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"), 
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("test") })), 
-            (String::from("Pid# 999 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 999 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 999,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("test") })),
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 000000us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("test") })), 
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 999 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 999 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 999,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("test") })),
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# user Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# user Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("user"), 
+                logger: String::from("user"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("test") })),
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# csiv2 Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# csiv2 Fil# vorb.C Lin# 1 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("csiv2"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("test") })),
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vdelegate.C Lin# 1 Lvl# INFO Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vdelegate.C Lin# 1 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vdelegate.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("test") })),
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 999 Lvl# INFO Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 999 Lvl# INFO Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 999,
                 level: LogLevel::INFO,
-                message: String::from("test") })),
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# ERROR Msg# test"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# ERROR Msg# test"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::ERROR,
-                message: String::from("test") })),
-            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# example"), 
+                message: String::from("test"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
+            (String::from("Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# example"),
             Ok(Log{
                 pid: 1,
                 time: NaiveDateTime::parse_from_str("Tue Jul  9 09:09:27 2024 612542us","%a %b %e %H:%M:%S %Y %fus").unwrap(),
                 tid: 1,
-                logger: String::from("default"), 
+                logger: String::from("default"),
                 component: String::from("server"),
                 file: String::from("vorb.C"),
                 line: 1,
                 level: LogLevel::INFO,
-                message: String::from("example") })),
+                message: String::from("example"),
+                source: Source::default(),
+                pattern_id: None,
+                fields: BTreeMap::new() })),
         ];
 
         for (input, output) in cases {
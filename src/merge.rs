@@ -0,0 +1,138 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
+
+use chrono::NaiveDateTime;
+
+use crate::{Log, LogError, LogStream};
+
+/// The next unread message from one of the inputs being merged
+struct Head {
+    time: NaiveDateTime,
+    input: usize,
+    log: Log,
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.time, self.input).cmp(&(other.time, other.input))
+    }
+}
+
+/// Interleaves the messages of several inputs in the order of their time.
+///
+/// Each input is expected to already be in time order, only the next message
+/// of every input is held in memory. Messages with the same time are returned
+/// in the order their inputs were given.
+pub(crate) struct MergedLogs {
+    inputs: Vec<LogStream>,
+    heads: BinaryHeap<Reverse<Head>>,
+    started: bool,
+}
+
+impl MergedLogs {
+    pub fn new(inputs: Vec<LogStream>) -> Self {
+        MergedLogs {
+            heads: BinaryHeap::with_capacity(inputs.len()),
+            inputs,
+            started: false,
+        }
+    }
+
+    fn advance(&mut self, input: usize) -> Result<(), LogError> {
+        if let Some(log) = self.inputs[input].next() {
+            let log = log?;
+            self.heads.push(Reverse(Head {
+                time: log.time,
+                input,
+                log,
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for MergedLogs {
+    type Item = Result<Log, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            for input in 0..self.inputs.len() {
+                if let Err(err) = self.advance(input) {
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        let Reverse(head) = self.heads.pop()?;
+        if let Err(err) = self.advance(head.input) {
+            return Some(Err(err));
+        }
+        Some(Ok(head.log))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reader::LogReader;
+
+    fn stream(text: &'static str, name: &str) -> LogStream {
+        Box::new(LogReader::new(text.as_bytes(), name))
+    }
+
+    #[test]
+    fn merged_logs_order() {
+        let client = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# a
+Pid# 1 Tim# Tue Jul  9 09:09:29 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# c
+Pid# 1 Tim# Tue Jul  9 09:09:30 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# INFO Msg# d
+";
+        let server = "\
+Pid# 2 Tim# Tue Jul  9 09:09:28 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# b
+Pid# 2 Tim# Tue Jul  9 09:09:30 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# e
+Pid# 2 Tim# Tue Jul  9 09:09:31 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# f
+";
+
+        let merged: Vec<(String, String)> = MergedLogs::new(vec![
+            stream(client, "client.log"),
+            stream(server, "server.log"),
+        ])
+        .map(|log| {
+            let log = log.unwrap();
+            (log.message, log.source.input)
+        })
+        .collect();
+
+        let expected = vec![
+            ("a", "client.log"),
+            ("b", "server.log"),
+            ("c", "client.log"),
+            ("d", "client.log"),
+            ("e", "server.log"),
+            ("f", "server.log"),
+        ];
+
+        assert_eq!(merged.len(), expected.len());
+        for ((message, input), (expected_message, expected_input)) in merged.iter().zip(expected) {
+            assert_eq!(message, expected_message);
+            assert_eq!(input, expected_input);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::{fs, io::Read, thread};
+
+use memmap2::Mmap;
+
+use crate::{index::Window, input, reader::LogReader, Log, LogError};
+
+/// Files smaller than this are not worth splitting up
+const PARALLEL_THRESHOLD: u64 = 32 * 1024 * 1024;
+/// Roughly how much of the file each thread reads at a time
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Checks if a file can be memory mapped and split up to be read in parallel,
+/// i.e. it is a large file on disk that is not compressed
+pub(crate) fn can_map(path: &str) -> bool {
+    let large = fs::metadata(path)
+        .map(|m| m.is_file() && m.len() >= PARALLEL_THRESHOLD)
+        .unwrap_or(false);
+    if !large {
+        return false;
+    }
+
+    let mut header = [0; 8];
+    match fs::File::open(path).and_then(|mut handle| handle.read(&mut header)) {
+        Ok(size) => !input::is_compressed(&header[..size]),
+        Err(_) => false,
+    }
+}
+
+/// Finds where the first message at or after a position in the data starts
+fn record_start(data: &[u8], from: usize) -> usize {
+    if from == 0 || from >= data.len() {
+        return from.min(data.len());
+    }
+
+    let marker = b"\nPid#";
+    data[from - 1..]
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .map(|index| from + index)
+        .unwrap_or(data.len())
+}
+
+/// The messages found in part of the file that where kept, along with how many
+/// lines that part of the file has so the line numbers can be fixed up after
+struct Chunk {
+    logs: Result<Vec<Log>, LogError>,
+    lines: usize,
+}
+
+fn read_chunk<K>(data: &[u8], start: usize, input: &str, keep: &K) -> Chunk
+where
+    K: Fn(&mut Log) -> bool + Sync,
+{
+    let lines = data.iter().filter(|byte| **byte == b'\n').count();
+    let mut logs = Vec::new();
+    for log in LogReader::new(data, input).with_position(Some(0), start) {
+        match log {
+            Ok(mut log) => {
+                if keep(&mut log) {
+                    logs.push(log);
+                }
+            }
+            Err(err) => {
+                return Chunk {
+                    logs: Err(err),
+                    lines,
+                }
+            }
+        }
+    }
+    Chunk {
+        logs: Ok(logs),
+        lines,
+    }
+}
+
+/// Parses the number of threads, which has to be at least one
+pub(crate) fn parse_jobs(text: &str) -> Result<usize, String> {
+    match text.parse() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(format!("{} is not a valid number of threads", text)),
+    }
+}
+
+/// Reads the messages in `data` which starts `offset` bytes and `lines` lines into the file
+#[allow(clippy::too_many_arguments)]
+fn read_chunks<K, F>(
+    data: &[u8],
+    input: &str,
+    offset: usize,
+    mut lines: Option<usize>,
+    jobs: usize,
+    chunk_size: usize,
+    keep: &K,
+    output: &mut F,
+) -> Result<(), LogError>
+where
+    K: Fn(&mut Log) -> bool + Sync,
+    F: FnMut(Log),
+{
+    // at least one chunk has to be read each time round or this never finishes
+    let jobs = jobs.max(1);
+    let mut start = 0;
+
+    while start < data.len() {
+        let mut bounds = Vec::with_capacity(jobs);
+        while bounds.len() < jobs && start < data.len() {
+            let end = record_start(data, start + chunk_size);
+            bounds.push((start, end));
+            start = end;
+        }
+
+        let chunks: Vec<Chunk> = thread::scope(|scope| {
+            let handles: Vec<_> = bounds
+                .iter()
+                .map(|(start, end)| {
+                    scope.spawn(move || {
+                        read_chunk(&data[*start..*end], offset + *start, input, keep)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("Failed to read part of the file"))
+                .collect()
+        });
+
+        for chunk in chunks {
+            for mut log in chunk.logs? {
+                log.source.line = lines.map_or(0, |lines| log.source.line + lines);
+                output(log);
+            }
+            lines = lines.map(|lines| lines + chunk.lines);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a large file by memory mapping it and splitting it up into chunks at
+/// the start of a message which are then read and filtered on several threads.
+///
+/// `keep` can fill in the extra fields of a message before deciding whether to
+/// keep it, the messages that are kept are passed to `output` in the order they
+/// appear in the file, if a window is given only that part of the file is read.
+pub(crate) fn read<K, F>(
+    path: &str,
+    window: Option<Window>,
+    jobs: usize,
+    keep: K,
+    mut output: F,
+) -> Result<(), LogError>
+where
+    K: Fn(&mut Log) -> bool + Sync,
+    F: FnMut(Log),
+{
+    let handle = fs::File::open(path).map_err(|err| LogError {
+        cause: format!("Unable to open {}: {}", path, err),
+    })?;
+    // Safety: the file is only read, as with any other reader if it is changed
+    // while being read the messages read may be garbled
+    let data = unsafe { Mmap::map(&handle) }.map_err(|err| LogError {
+        cause: format!("Unable to memory map {}: {}", path, err),
+    })?;
+
+    let (offset, line, end) = match window {
+        Some(window) => (window.offset, window.line, window.end.unwrap_or(data.len())),
+        None => (0, Some(0), data.len()),
+    };
+    let data = data.get(offset..end).unwrap_or_default();
+
+    read_chunks(
+        data,
+        path,
+        offset,
+        line,
+        jobs,
+        CHUNK_SIZE,
+        &keep,
+        &mut output,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{logs, record};
+
+    #[test]
+    fn read_chunks_order() {
+        let mut text = String::new();
+        for index in 0..100usize {
+            text.push_str(&record(0).pid(index).message("test"));
+            if index.is_multiple_of(7) {
+                text.push_str("  continued\n");
+            }
+        }
+
+        let expected: Vec<Log> = logs(&text)
+            .into_iter()
+            .filter(|log| log.pid.is_multiple_of(3))
+            .collect();
+
+        // including more jobs than there are chunks to read
+        for (jobs, chunk_size) in [
+            (1, 10_000),
+            (4, 50),
+            (3, 333),
+            (8, 1),
+            (0, 100),
+            (16, 10_000),
+            (64, 2_000),
+        ] {
+            let mut logs = Vec::new();
+            read_chunks(
+                text.as_bytes(),
+                "test.log",
+                0,
+                Some(0),
+                jobs,
+                chunk_size,
+                &|log: &mut Log| log.pid.is_multiple_of(3),
+                &mut |log| logs.push(log),
+            )
+            .unwrap();
+            assert_eq!(
+                logs, expected,
+                "Checking {} jobs with {} byte chunks",
+                jobs, chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn read_chunks_empty() {
+        for data in [&b""[..], &b"\n\n"[..]] {
+            let mut logs = Vec::new();
+            read_chunks(
+                data,
+                "test.log",
+                0,
+                Some(0),
+                4,
+                10,
+                &|_: &mut Log| true,
+                &mut |log| logs.push(log),
+            )
+            .unwrap();
+            assert!(logs.is_empty());
+        }
+    }
+}
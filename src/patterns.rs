@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use crate::{collapse::NUMBER_PATTERN, Log};
+
+/// What each variable part of a message is replaced with before the messages
+/// are grouped, in the order they are replaced
+const MASKS: [(&str, &str); 4] = [
+    (r"IOR:[0-9a-fA-F]+", "<IOR>"),
+    (r#""[^"]*""#, "<STR>"),
+    (r"'[^']*'", "<STR>"),
+    (NUMBER_PATTERN, "<NUM>"),
+];
+/// The placeholder for a token that differs between the messages of a template
+const WILDCARD: &str = "<*>";
+/// How many of the first tokens of a message are used to narrow down the
+/// templates it is compared with
+const PREFIX_TOKENS: usize = 2;
+/// The fraction of tokens that have to be the same for a message to belong to a template
+const SIMILARITY: f64 = 0.5;
+
+/// A group of messages that only differ in their variable parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Template {
+    pub id: usize,
+    pub tokens: Vec<String>,
+    pub count: usize,
+    pub first: NaiveDateTime,
+    pub last: NaiveDateTime,
+}
+
+impl Template {
+    pub fn text(&self) -> String {
+        self.tokens.join(" ")
+    }
+
+    /// How many tokens of a message are the same as the template, the
+    /// wildcards are not counted
+    fn similarity(&self, tokens: &[String]) -> (f64, usize) {
+        let same = self
+            .tokens
+            .iter()
+            .zip(tokens)
+            .filter(|(template, token)| template == token)
+            .count();
+        let wildcards = self
+            .tokens
+            .iter()
+            .filter(|token| *token == WILDCARD)
+            .count();
+        (same as f64 / tokens.len().max(1) as f64, wildcards)
+    }
+}
+
+/// Groups messages into templates by replacing their variable parts with
+/// placeholders, using a version of the Drain algorithm.
+///
+/// Messages are first masked (IORs, quoted strings, numbers and hex ids are
+/// replaced) and split into tokens. Only the templates with the same number of
+/// tokens and the same first few tokens are compared with a message, if enough
+/// of its tokens are the same as the closest one it joins that template and any
+/// tokens that differ become wildcards, otherwise it starts a new template.
+///
+/// Templates are numbered in the order they are first seen so the ids are the
+/// same every time the same messages are read.
+pub(crate) struct Miner {
+    masks: Vec<(Regex, &'static str)>,
+    /// the templates in the order they were first seen
+    templates: Vec<Template>,
+    /// the templates with each number of tokens and prefix
+    groups: HashMap<(usize, Vec<String>), Vec<usize>>,
+}
+
+impl Default for Miner {
+    fn default() -> Self {
+        Miner {
+            masks: MASKS
+                .iter()
+                .map(|(pattern, mask)| (Regex::new(pattern).expect("Invalid mask pattern"), *mask))
+                .collect(),
+            templates: Vec::new(),
+            groups: HashMap::new(),
+        }
+    }
+}
+
+impl Miner {
+    fn tokens(&self, message: &str) -> Vec<String> {
+        let mut message = message.to_string();
+        for (pattern, mask) in &self.masks {
+            message = pattern.replace_all(&message, *mask).to_string();
+        }
+        message.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn prefix(tokens: &[String]) -> Vec<String> {
+        tokens
+            .iter()
+            .take(PREFIX_TOKENS)
+            .map(|token| {
+                if token.starts_with('<') || token.chars().any(|c| c.is_ascii_digit()) {
+                    WILDCARD.to_string()
+                } else {
+                    token.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Adds a message to the template it belongs to, returning the id of the template
+    pub fn add(&mut self, log: &Log) -> usize {
+        let tokens = self.tokens(&log.message);
+        let key = (tokens.len(), Miner::prefix(&tokens));
+        let group = self.groups.entry(key).or_default();
+
+        let closest = group
+            .iter()
+            .map(|index| (*index, self.templates[*index].similarity(&tokens)))
+            .filter(|(_, (similarity, _))| *similarity >= SIMILARITY || tokens.is_empty())
+            .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)))
+            .map(|(index, _)| index);
+
+        match closest {
+            Some(index) => {
+                let template = &mut self.templates[index];
+                for (template, token) in template.tokens.iter_mut().zip(&tokens) {
+                    if template != token {
+                        *template = WILDCARD.to_string();
+                    }
+                }
+                template.count += 1;
+                template.first = template.first.min(log.time);
+                template.last = template.last.max(log.time);
+                template.id
+            }
+            None => {
+                let id = self.templates.len() + 1;
+                group.push(self.templates.len());
+                self.templates.push(Template {
+                    id,
+                    tokens,
+                    count: 1,
+                    first: log.time,
+                    last: log.time,
+                });
+                id
+            }
+        }
+    }
+
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+}
+
+/// How many of the messages kept belong to a template and when they were logged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Seen {
+    count: usize,
+    first: NaiveDateTime,
+    last: NaiveDateTime,
+}
+
+/// A report of the templates of the messages kept with a few examples of each.
+///
+/// The templates come from a miner that has seen every message read, not only
+/// the ones kept, so their ids are the same whichever filters are used.
+#[derive(Default)]
+pub(crate) struct Patterns {
+    seen: HashMap<usize, Seen>,
+    examples: HashMap<usize, Vec<Log>>,
+}
+
+impl Patterns {
+    /// Adds a message that has already been given its template id
+    pub fn add(&mut self, log: Log, examples: usize) {
+        let Some(id) = log.pattern_id else {
+            return;
+        };
+        self.seen
+            .entry(id)
+            .and_modify(|seen| {
+                seen.count += 1;
+                seen.first = seen.first.min(log.time);
+                seen.last = seen.last.max(log.time);
+            })
+            .or_insert(Seen {
+                count: 1,
+                first: log.time,
+                last: log.time,
+            });
+        let kept = self.examples.entry(id).or_default();
+        if kept.len() < examples {
+            kept.push(log);
+        }
+    }
+
+    /// The templates of the messages kept with the most messages first
+    fn templates<'a>(&self, miner: &'a Miner) -> Vec<(&'a Template, Seen)> {
+        let mut templates: Vec<(&Template, Seen)> = miner
+            .templates()
+            .iter()
+            .filter_map(|template| self.seen.get(&template.id).map(|seen| (template, *seen)))
+            .collect();
+        templates.sort_by(|(a, a_seen), (b, b_seen)| {
+            b_seen.count.cmp(&a_seen.count).then(a.id.cmp(&b.id))
+        });
+        templates
+    }
+
+    /// Prints the `top` most common templates (or all of them) along with
+    /// their examples
+    pub fn print(&self, miner: &Miner, top: Option<usize>, date_fmt: &str) {
+        let templates = self.templates(miner);
+        let top = top.unwrap_or(templates.len());
+
+        for (template, seen) in templates.into_iter().take(top) {
+            println!(
+                "#{}  {} messages  {} - {}",
+                template.id,
+                seen.count,
+                seen.first.format(date_fmt),
+                seen.last.format(date_fmt)
+            );
+            println!("    {}", template.text());
+            for example in self.examples.get(&template.id).into_iter().flatten() {
+                println!(
+                    "      {}: {}",
+                    example.source,
+                    example.message.lines().next().unwrap_or_default()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    #[test]
+    fn mine_templates() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# *** Server Interceptor installed for POA: \"/exampleSERVER\"
+Pid# 1 Tim# Tue Jul  9 09:09:28 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# *** Server Interceptor installed for POA: \"/otherSERVER\"
+Pid# 1 Tim# Tue Jul  9 09:09:29 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# connection 12 to host alpha closed
+Pid# 1 Tim# Tue Jul  9 09:09:30 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# connection 0x1f to host beta closed
+Pid# 1 Tim# Tue Jul  9 09:09:31 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# object IOR:000abc01 activated
+Pid# 1 Tim# Tue Jul  9 09:09:32 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# connection refused by host gamma now
+";
+        let mut miner = Miner::default();
+        let mut patterns = Patterns::default();
+        let ids: Vec<usize> = logs(text)
+            .into_iter()
+            .map(|mut log| {
+                let id = miner.add(&log);
+                // only some of the messages are kept but every one is mined
+                if log.message.starts_with("connection") {
+                    log.pattern_id = Some(id);
+                    patterns.add(log, 1);
+                }
+                id
+            })
+            .collect();
+
+        assert_eq!(ids, vec![1, 1, 2, 2, 3, 4]);
+        let templates: Vec<(String, usize)> = miner
+            .templates()
+            .iter()
+            .map(|template| (template.text(), template.count))
+            .collect();
+        assert_eq!(
+            templates,
+            vec![
+                (
+                    String::from("*** Server Interceptor installed for POA: <STR>"),
+                    2
+                ),
+                (String::from("connection <NUM> to host <*> closed"), 2),
+                (String::from("object <IOR> activated"), 1),
+                (String::from("connection refused by host gamma now"), 1),
+            ]
+        );
+
+        let kept: Vec<(usize, usize)> = patterns
+            .templates(&miner)
+            .iter()
+            .map(|(template, seen)| (template.id, seen.count))
+            .collect();
+        assert_eq!(kept, vec![(2, 2), (4, 1)]);
+    }
+}
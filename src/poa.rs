@@ -0,0 +1,290 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+use vislog::ior::{self, Profile};
+
+use crate::Log;
+
+/// Matches the name of a POA, quoted or as a path e.g. `POA: "/exampleSERVER"`
+const POA_PATTERN: &str = r#"(?i)\bPOA(?:\s+name)?\s*[:=]?\s*(?:"(?P<quoted>[^"]*)"|'(?P<single>[^']*)'|(?P<path>/[^\s,;]*))"#;
+/// Matches the id of an object e.g. `oid=account1` or `object id: "account1"`
+const OBJECT_ID_PATTERN: &str =
+    r#"(?i)\b(?:oid|object\s*id)\s*[:=]?\s*(?:"(?P<quoted>[^"]*)"|(?P<plain>[^\s,;"]+))"#;
+/// What separates the POA path and the object id in an escaped object key
+const KEY_SEPARATOR: &str = "\\x00";
+
+/// What a message says happened to a POA or object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Activated,
+    Deactivated,
+    Destroyed,
+    Other,
+}
+
+impl Event {
+    fn of(message: &str) -> Self {
+        let message = message.to_lowercase();
+        if message.contains("deactivat") || message.contains("etherealiz") {
+            Event::Deactivated
+        } else if message.contains("destroy") {
+            Event::Destroyed
+        } else if message.contains("activat") || message.contains("incarnat") {
+            Event::Activated
+        } else {
+            Event::Other
+        }
+    }
+}
+
+/// What has been seen of a POA or an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Activity {
+    messages: usize,
+    activations: usize,
+    deactivations: usize,
+    destroyed: bool,
+    first: NaiveDateTime,
+    last: NaiveDateTime,
+}
+
+impl Activity {
+    fn new(time: NaiveDateTime) -> Self {
+        Activity {
+            messages: 0,
+            activations: 0,
+            deactivations: 0,
+            destroyed: false,
+            first: time,
+            last: time,
+        }
+    }
+
+    fn add(&mut self, time: NaiveDateTime, event: Event) {
+        self.messages += 1;
+        self.first = self.first.min(time);
+        self.last = self.last.max(time);
+        match event {
+            Event::Activated => self.activations += 1,
+            Event::Deactivated => self.deactivations += 1,
+            Event::Destroyed => self.destroyed = true,
+            Event::Other => {}
+        }
+    }
+
+    fn describe(&self, date_fmt: &str) -> String {
+        let mut description = format!(
+            "{} messages, {} - {}",
+            self.messages,
+            self.first.format(date_fmt),
+            self.last.format(date_fmt)
+        );
+        if self.activations > 0 {
+            description.push_str(&format!(", activated {}", self.activations));
+        }
+        if self.deactivations > 0 {
+            description.push_str(&format!(", deactivated {}", self.deactivations));
+        }
+        if self.destroyed {
+            description.push_str(", destroyed");
+        }
+        description
+    }
+}
+
+/// A POA along with the objects seen in it
+#[derive(Debug)]
+struct Poa {
+    activity: Activity,
+    objects: BTreeMap<String, Activity>,
+}
+
+/// The segments of a POA name, so the POAs sort into their hierarchy
+fn segments(name: &str) -> Vec<String> {
+    name.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Splits a VisiBroker object key (escaped as by the IOR decoding) into the
+/// path of the POA and the id of the object, the two are separated by nulls
+fn split_key(key: &str) -> Option<(String, String)> {
+    let start = key.find('/')?;
+    let poa = &key[start..];
+    let end = poa.find(KEY_SEPARATOR).unwrap_or(poa.len());
+    let id = poa[end..]
+        .rsplit(KEY_SEPARATOR)
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    Some((poa[..end].to_string(), id))
+}
+
+/// Builds the POA hierarchy of each process from the POA names, object ids and
+/// object keys found in the messages
+pub(crate) struct Poas {
+    poa: Regex,
+    object_id: Regex,
+    /// keyed by pid and the segments of the POA name
+    poas: BTreeMap<usize, BTreeMap<Vec<String>, Poa>>,
+}
+
+impl Poas {
+    pub fn new() -> Self {
+        Poas {
+            poa: Regex::new(POA_PATTERN).expect("Invalid POA pattern"),
+            object_id: Regex::new(OBJECT_ID_PATTERN).expect("Invalid object id pattern"),
+            poas: BTreeMap::new(),
+        }
+    }
+
+    /// The POAs and objects a message is about, first from its text and then
+    /// from the object keys of any IORs in it
+    fn find(&self, message: &str) -> Vec<(String, Option<String>)> {
+        let mut found = Vec::new();
+        if let Some(captures) = self.poa.captures(message) {
+            let name = ["quoted", "single", "path"]
+                .iter()
+                .find_map(|name| captures.name(name))
+                .map(|name| name.as_str().to_string())
+                .unwrap_or_default();
+            let object = self.object_id.captures(message).and_then(|captures| {
+                captures
+                    .name("quoted")
+                    .or(captures.name("plain"))
+                    .map(|id| id.as_str().to_string())
+            });
+            found.push((name, object));
+        }
+
+        for ior in ior::find(message).filter_map(|ior| ior::decode(ior).ok()) {
+            for profile in ior.profiles {
+                if let Profile::Iiop { object_key, .. } = profile {
+                    if let Some((poa, id)) = split_key(&object_key) {
+                        found.push((poa, (!id.is_empty()).then_some(id)));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        let found = self.find(&log.message);
+        if found.is_empty() {
+            return;
+        }
+
+        let event = Event::of(&log.message);
+        let poas = self.poas.entry(log.pid).or_default();
+        for (name, object) in found {
+            let poa = poas.entry(segments(&name)).or_insert_with(|| Poa {
+                activity: Activity::new(log.time),
+                objects: BTreeMap::new(),
+            });
+            match object {
+                Some(object) => {
+                    // the event is about the object, the POA was only used
+                    poa.activity.add(log.time, Event::Other);
+                    poa.objects
+                        .entry(object)
+                        .or_insert_with(|| Activity::new(log.time))
+                        .add(log.time, event);
+                }
+                None => poa.activity.add(log.time, event),
+            }
+        }
+    }
+
+    /// Prints the POAs of each process as a tree with the objects under them
+    pub fn print(&self, date_fmt: &str) {
+        for (index, (pid, poas)) in self.poas.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            println!("pid {}", pid);
+
+            // the parents of a POA are shown even if nothing mentioned them
+            let mut paths = BTreeSet::new();
+            for path in poas.keys() {
+                for depth in 1..=path.len() {
+                    paths.insert(path[..depth].to_vec());
+                }
+            }
+            if poas.contains_key(&Vec::new()) {
+                paths.insert(Vec::new());
+            }
+
+            for path in paths {
+                let indent = "  ".repeat(path.len().max(1));
+                let name = format!("/{}", path.join("/"));
+                match poas.get(&path) {
+                    Some(poa) => {
+                        println!("{}{}  {}", indent, name, poa.activity.describe(date_fmt));
+                        for (id, object) in &poa.objects {
+                            println!("{}  object {}  {}", indent, id, object.describe(date_fmt));
+                        }
+                    }
+                    None => println!("{}{}  not seen", indent, name),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    #[test]
+    fn build_hierarchy() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# *** Server Interceptor installed for POA: \"/exampleSERVER\"
+Pid# 1 Tim# Tue Jul  9 09:09:28 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# POA /exampleSERVER/accounts/savings created
+Pid# 1 Tim# Tue Jul  9 09:09:29 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# activated object oid=acc1 in POA \"/exampleSERVER/accounts/savings\"
+Pid# 1 Tim# Tue Jul  9 09:09:30 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# deactivated object oid=acc1 in POA \"/exampleSERVER/accounts/savings\"
+Pid# 2 Tim# Tue Jul  9 09:09:31 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# POA: \"/exampleSERVER\" destroyed
+Pid# 2 Tim# Tue Jul  9 09:09:32 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# nothing about adapters here
+";
+        let mut poas = Poas::new();
+        for log in logs(text) {
+            poas.add(&log);
+        }
+
+        let names: Vec<(usize, Vec<String>)> = poas
+            .poas
+            .iter()
+            .flat_map(|(pid, poas)| poas.keys().map(|path| (*pid, path.clone())))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (1, segments("/exampleSERVER")),
+                (1, segments("/exampleSERVER/accounts/savings")),
+                (2, segments("/exampleSERVER")),
+            ]
+        );
+
+        let savings = &poas.poas[&1][&segments("/exampleSERVER/accounts/savings")];
+        assert_eq!(savings.activity.messages, 3);
+        let object = &savings.objects["acc1"];
+        assert_eq!((object.activations, object.deactivations), (1, 1));
+        assert_eq!(object.last - object.first, chrono::TimeDelta::seconds(1));
+        assert!(
+            poas.poas[&2][&segments("/exampleSERVER")]
+                .activity
+                .destroyed
+        );
+
+        assert_eq!(
+            split_key(
+                "\\x00PMC\\x00\\x00\\x00\\x04\\x00\\x00\\x00\\x0c/bank_poa\\x00\\x00account1"
+            ),
+            Some((String::from("/bank_poa"), String::from("account1")))
+        );
+        assert_eq!(split_key("NameService"), None);
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::{
+    gaps::print_message,
+    histogram::{level_index, Counts},
+    stats::format_span,
+    Log, LogLevel,
+};
+
+/// What the messages a process logs as it starts contain, lowercase
+const STARTUP_BANNERS: [&str; 7] = [
+    "orb_init",
+    "orb initialized",
+    "orb initialised",
+    "starting up",
+    "server started",
+    "server ready",
+    "server is ready",
+];
+/// What the messages a process logs as it shuts down cleanly contain, lowercase
+const SHUTDOWN_BANNERS: [&str; 8] = [
+    "orb shutdown",
+    "orb::shutdown",
+    "orb.shutdown",
+    "orb shut down",
+    "shutting down",
+    "shutdown complete",
+    "server stopped",
+    "exiting normally",
+];
+
+/// The banner in a message, if it has one
+fn banner(message: &str, banners: &[&'static str]) -> Option<&'static str> {
+    let message = message.to_lowercase();
+    banners
+        .iter()
+        .find(|banner| message.contains(*banner))
+        .copied()
+}
+
+/// One run of a process, a pid can have several if it is reused
+#[derive(Debug)]
+struct Incarnation {
+    pid: usize,
+    /// the component of the first record
+    component: String,
+    first: NaiveDateTime,
+    last: NaiveDateTime,
+    counts: Counts,
+    /// when the first startup banner was seen and what it was
+    started: Option<(NaiveDateTime, &'static str)>,
+    shutdown: Option<NaiveDateTime>,
+    /// the last records it logged
+    tail: VecDeque<Log>,
+}
+
+impl Incarnation {
+    fn new(log: &Log) -> Self {
+        Incarnation {
+            pid: log.pid,
+            component: log.component.clone(),
+            first: log.time,
+            last: log.time,
+            counts: Counts::default(),
+            started: None,
+            shutdown: None,
+            tail: VecDeque::new(),
+        }
+    }
+
+    /// If a startup banner means the pid has been reused by a new process,
+    /// either this one already shut down or it already went through the same
+    /// step of starting up
+    fn restarted_by(&self, startup: &str) -> bool {
+        self.shutdown.is_some() || self.started.is_some_and(|(_, banner)| banner == startup)
+    }
+}
+
+/// Lists each process with when it logged, how much at each level and whether
+/// it shut down cleanly, spotting restarts and probable crashes
+pub(crate) struct Processes {
+    /// how many of the last records of a probable crash are kept
+    last: usize,
+    /// how close to the end of the logs a process has to have logged to be
+    /// taken as still running
+    running: TimeDelta,
+    incarnations: Vec<Incarnation>,
+    /// the index of the latest incarnation of each pid
+    current: HashMap<usize, usize>,
+    latest: Option<NaiveDateTime>,
+}
+
+impl Processes {
+    pub fn new(last: usize, running: TimeDelta) -> Self {
+        Processes {
+            last,
+            running,
+            incarnations: Vec::new(),
+            current: HashMap::new(),
+            latest: None,
+        }
+    }
+
+    pub fn add(&mut self, log: &Log) {
+        let startup = banner(&log.message, &STARTUP_BANNERS);
+        let index = match self.current.get(&log.pid) {
+            Some(index)
+                if !startup
+                    .is_some_and(|startup| self.incarnations[*index].restarted_by(startup)) =>
+            {
+                *index
+            }
+            _ => {
+                self.incarnations.push(Incarnation::new(log));
+                self.current.insert(log.pid, self.incarnations.len() - 1);
+                self.incarnations.len() - 1
+            }
+        };
+
+        let incarnation = &mut self.incarnations[index];
+        incarnation.first = incarnation.first.min(log.time);
+        incarnation.last = incarnation.last.max(log.time);
+        incarnation.counts[level_index(&log.level)] += 1;
+        if let Some(startup) = startup {
+            incarnation.started.get_or_insert((log.time, startup));
+        }
+        if banner(&log.message, &SHUTDOWN_BANNERS).is_some() {
+            incarnation.shutdown = Some(log.time);
+        }
+        if self.last > 0 {
+            incarnation.tail.push_back(log.clone());
+            if incarnation.tail.len() > self.last {
+                incarnation.tail.pop_front();
+            }
+        }
+        self.latest = self.latest.max(Some(log.time));
+    }
+
+    /// The process that ran the same component before this one, if it had
+    /// stopped logging by the time this one started
+    fn restart_of(&self, index: usize) -> Option<&Incarnation> {
+        let incarnation = &self.incarnations[index];
+        self.incarnations
+            .iter()
+            .enumerate()
+            .filter(|(other_index, other)| {
+                *other_index != index
+                    && other.component == incarnation.component
+                    && other.last <= incarnation.first
+            })
+            .map(|(_, other)| other)
+            .max_by_key(|other| other.last)
+    }
+
+    /// If the process stopped logging without a shutdown message, either its
+    /// pid was reused or it logged nothing near the end of the logs
+    fn crashed(&self, index: usize) -> bool {
+        let incarnation = &self.incarnations[index];
+        let stopped = self
+            .latest
+            .is_some_and(|latest| latest - incarnation.last > self.running);
+        incarnation.shutdown.is_none() && (self.current[&incarnation.pid] != index || stopped)
+    }
+
+    pub fn print(&self, date_fmt: &str) {
+        let mut order: Vec<usize> = (0..self.incarnations.len()).collect();
+        order.sort_by_key(|index| (self.incarnations[*index].first, *index));
+
+        for (position, index) in order.into_iter().enumerate() {
+            if position > 0 {
+                println!();
+            }
+            let incarnation = &self.incarnations[index];
+            let counts: Vec<String> = LogLevel::ALL
+                .iter()
+                .zip(incarnation.counts)
+                .filter(|(_, count)| *count > 0)
+                .map(|(level, count)| format!("{} {}", level, count))
+                .collect();
+            println!(
+                "pid {} {}: {} - {} ({}), {} records: {}",
+                incarnation.pid,
+                incarnation.component,
+                incarnation.first.format(date_fmt),
+                incarnation.last.format(date_fmt),
+                format_span(incarnation.last - incarnation.first),
+                incarnation.counts.iter().sum::<usize>(),
+                counts.join(", ")
+            );
+
+            if let Some(previous) = self.restart_of(index) {
+                println!(
+                    "  restart of pid {} which last logged at {}",
+                    previous.pid,
+                    previous.last.format(date_fmt)
+                );
+            }
+            if let Some((started, _)) = incarnation.started {
+                println!("  started at {}", started.format(date_fmt));
+            }
+            if let Some(shutdown) = incarnation.shutdown {
+                println!("  shut down at {}", shutdown.format(date_fmt));
+            } else if self.crashed(index) {
+                println!(
+                    "  ended without a shutdown message, probable crash, last {} records:",
+                    incarnation.tail.len()
+                );
+                for log in &incarnation.tail {
+                    print_message("", log, date_fmt);
+                }
+            } else {
+                println!("  still logging at the end");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{logs, record};
+
+    #[test]
+    fn find_incarnations() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:00 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# ORB_init called
+Pid# 1 Tim# Tue Jul  9 09:09:01 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# Server is ready
+Pid# 1 Tim# Tue Jul  9 09:09:02 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# ERROR Msg# out of memory
+Pid# 2 Tim# Tue Jul  9 09:09:05 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# ORB_init called
+Pid# 2 Tim# Tue Jul  9 09:09:06 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# ORB shutdown requested
+Pid# 2 Tim# Tue Jul  9 09:09:07 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# ORB_init called
+Pid# 3 Tim# Tue Jul  9 09:09:08 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 1 Lvl# DEBUG Msg# still going
+";
+        let mut processes = Processes::new(2, TimeDelta::seconds(1));
+        let mut untailed = Processes::new(0, TimeDelta::seconds(1));
+        for log in logs(text) {
+            processes.add(&log);
+            untailed.add(&log);
+        }
+        assert!(untailed
+            .incarnations
+            .iter()
+            .all(|incarnation| incarnation.tail.is_empty()));
+
+        let incarnations: Vec<(usize, usize, bool, bool)> = processes
+            .incarnations
+            .iter()
+            .enumerate()
+            .map(|(index, incarnation)| {
+                (
+                    incarnation.pid,
+                    incarnation.counts.iter().sum(),
+                    incarnation.shutdown.is_some(),
+                    processes.crashed(index),
+                )
+            })
+            .collect();
+        assert_eq!(
+            incarnations,
+            vec![
+                (1, 3, false, true),
+                (2, 2, true, false),
+                (2, 1, false, false),
+                (3, 1, false, false),
+            ]
+        );
+
+        let crashed = &processes.incarnations[0];
+        assert_eq!(crashed.counts[level_index(&LogLevel::ERROR)], 1);
+        let tail: Vec<&str> = crashed
+            .tail
+            .iter()
+            .map(|log| log.message.as_str())
+            .collect();
+        assert_eq!(tail, vec!["Server is ready", "out of memory"]);
+
+        assert_eq!(
+            processes.restart_of(1).map(|previous| previous.pid),
+            Some(1)
+        );
+        assert!(processes.restart_of(3).is_none());
+    }
+
+    #[test]
+    fn pid_reused_without_banner() {
+        // without a startup banner a new process can't be told apart from an
+        // old one with the same pid, even one that shut down
+        let text = record(0).message("request handled")
+            + &record(3600).message("request handled")
+            + &record(3601).pid(2).message("shutting down")
+            + &record(7200).pid(2).message("request handled");
+        let mut processes = Processes::new(2, TimeDelta::seconds(1));
+        for log in logs(&text) {
+            processes.add(&log);
+        }
+
+        let incarnations: Vec<(usize, usize, bool)> = processes
+            .incarnations
+            .iter()
+            .map(|incarnation| {
+                (
+                    incarnation.pid,
+                    incarnation.counts.iter().sum(),
+                    incarnation.shutdown.is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(incarnations, vec![(1, 2, false), (2, 2, true)]);
+        assert!(processes.crashed(0));
+        assert!(!processes.crashed(1));
+    }
+}
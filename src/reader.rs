@@ -0,0 +1,192 @@
+use std::io::BufRead;
+
+use crate::{Log, LogError, Source};
+
+/// The marker found at the start of every visibroker log message
+pub const RECORD_MARKER: &str = "Pid#";
+
+/// The input name used for messages read from std::in
+pub const STDIN_NAME: &str = "(standard input)";
+
+/// Assembles lines of text into visibroker log messages.
+///
+/// A new message starts at every line beginning with `Pid#`, any other line is
+/// treated as a continuation of the message before it. As a message can only be
+/// known to be complete once the next one starts, the last message is held
+/// until either another one starts or it is flushed. Each message is tagged
+/// with the input, line number and byte offset it was read from.
+pub(crate) struct Records {
+    input: String,
+    /// the number of lines read so far, None if where reading started is not known
+    line: Option<usize>,
+    offset: usize,
+    pending: Option<(String, Source)>,
+}
+
+impl Records {
+    pub fn new(input: &str) -> Self {
+        Records {
+            input: input.to_string(),
+            line: Some(0),
+            offset: 0,
+            pending: None,
+        }
+    }
+
+    /// Adds a line of text, returning the previous message if this line starts a new one
+    pub fn push_line(&mut self, text: String) -> Option<Result<Log, LogError>> {
+        let source = Source {
+            input: self.input.clone(),
+            line: self.line.map_or(0, |line| line + 1),
+            offset: self.offset,
+        };
+        self.line = self.line.map(|line| line + 1);
+        self.offset += text.len();
+
+        if text.starts_with(RECORD_MARKER) {
+            if let Some((text, source)) = self.pending.replace((text, source)) {
+                return Some(parse(text, source));
+            }
+        } else if let Some((pending, _)) = self.pending.as_mut() {
+            pending.push_str(&text);
+        }
+        None
+    }
+
+    /// Returns the message currently being held if there is one
+    pub fn flush(&mut self) -> Option<Result<Log, LogError>> {
+        self.pending
+            .take()
+            .map(|(text, source)| parse(text, source))
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Moves the position of the next line read e.g. after skipping part of the
+    /// input, the line is None if the number of lines skipped is not known
+    pub fn seek(&mut self, line: Option<usize>, offset: usize) {
+        self.line = line;
+        self.offset = offset;
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// Where the next line starts, by line number if it is known
+    pub fn position(&self) -> String {
+        match self.line {
+            Some(line) => format!("on line {}", line + 1),
+            None => format!("at byte {}", self.offset),
+        }
+    }
+}
+
+/// Converts bytes read from an input into a line of text
+pub(crate) fn to_line(buffer: Vec<u8>, records: &Records) -> Result<String, LogError> {
+    String::from_utf8(buffer).map_err(|_| LogError {
+        cause: format!(
+            "data from {} is not utf-8 formatted {}",
+            records.input(),
+            records.position()
+        ),
+    })
+}
+
+/// Splits a stream of text into visibroker log messages see [`Records`]
+pub(crate) struct LogReader<R> {
+    reader: R,
+    records: Records,
+}
+
+impl<R: BufRead> LogReader<R> {
+    pub fn new(reader: R, input: &str) -> Self {
+        LogReader {
+            reader,
+            records: Records::new(input),
+        }
+    }
+
+    /// Sets the line number and byte offset the reader is starting from e.g.
+    /// when it is reading part way through an input
+    pub fn with_position(mut self, line: Option<usize>, offset: usize) -> Self {
+        self.records.seek(line, offset);
+        self
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, LogError> {
+        let mut buffer = Vec::new();
+        let size = self
+            .reader
+            .read_until(b'\n', &mut buffer)
+            .map_err(|err| LogError {
+                cause: format!("Failed to read from {}: {}", self.records.input(), err),
+            })?;
+
+        if size == 0 {
+            return Ok(None);
+        }
+
+        to_line(buffer, &self.records).map(Some)
+    }
+}
+
+fn parse(text: String, source: Source) -> Result<Log, LogError> {
+    let mut log = Log::from(text)?;
+    log.source = source;
+    Ok(log)
+}
+
+impl<R: BufRead> Iterator for LogReader<R> {
+    type Item = Result<Log, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.read_line() {
+                Ok(Some(text)) => {
+                    if let Some(log) = self.records.push_line(text) {
+                        return Some(log);
+                    }
+                }
+                Ok(None) => return self.records.flush(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn log_reader_source() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# first
+Pid# 2 Tim# Tue Jul  9 09:09:28 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# second
+  continued
+Pid# 3 Tim# Tue Jul  9 09:09:29 2024 612542us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# third";
+
+        let logs: Vec<Log> = LogReader::new(text.as_bytes(), "test.log")
+            .map(|log| log.unwrap())
+            .collect();
+
+        let first_len = text.find("Pid# 2").unwrap();
+        let third_start = text.find("Pid# 3").unwrap();
+        let cases = vec![
+            ("first", 1, 0),
+            ("second\n  continued", 2, first_len),
+            ("third", 4, third_start),
+        ];
+
+        assert_eq!(logs.len(), cases.len());
+        for (log, (message, line, offset)) in logs.iter().zip(cases) {
+            assert_eq!(log.message, message);
+            assert_eq!(log.source.input, "test.log");
+            assert_eq!(log.source.line, line);
+            assert_eq!(log.source.offset, offset);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::{cmp::Reverse, collections::VecDeque, fs, path::Path};
+
+use chrono::NaiveDateTime;
+use regex::Regex;
+
+use crate::{input, Log, LogError, LogStream};
+
+/// Matches the end of the name of a rotated log e.g. the `.1` of `app.log.1` or
+/// the `.2.gz` of `app.log.2.gz`
+const ROTATED_SUFFIX: &str = r"(\.(?P<number>\d+))?(\.(gz|zst|bz2))?$";
+/// Like `ROTATED_SUFFIX` but there has to be a suffix, so any other file is
+/// not taken for a rotated log
+const REQUIRED_SUFFIX: &str = r"(\.(?P<number>\d+)(\.(gz|zst|bz2))?|\.(gz|zst|bz2))$";
+
+fn rotated_name(base: Option<&str>) -> Regex {
+    let pattern = match base {
+        Some(base) => format!("^{}{}", regex::escape(base), ROTATED_SUFFIX),
+        None => format!("^(?P<base>.+?){}", REQUIRED_SUFFIX),
+    };
+    Regex::new(&pattern).unwrap()
+}
+
+/// A file from a rotated log set along with the time of its first message
+struct Rotation {
+    path: String,
+    first: NaiveDateTime,
+}
+
+fn list_dir(dir: &Path) -> Result<Vec<String>, LogError> {
+    let entries = fs::read_dir(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    })
+    .map_err(|err| LogError {
+        cause: format!("Unable to read directory {}: {}", dir.display(), err),
+    })?;
+
+    let mut names = Vec::new();
+    for entry in entries.flatten() {
+        if entry
+            .file_type()
+            .map(|kind| kind.is_file())
+            .unwrap_or(false)
+        {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Finds the rotated log sets for a path.
+///
+/// If the path is a directory every rotated set in it is returned, a set being
+/// the files with a rotation suffix and the file with their base name, otherwise
+/// the path is treated as the base name of a set and it is returned along with
+/// any rotated siblings e.g. `app.log.1` or `app.log.2.gz`.
+pub(crate) fn discover(path: &str) -> Result<Vec<Vec<String>>, LogError> {
+    let path = Path::new(path);
+
+    let (dir, base) = if path.is_dir() {
+        (path, None)
+    } else {
+        let base = path.file_name().ok_or_else(|| LogError {
+            cause: format!("{} is not a valid log file name", path.display()),
+        })?;
+        (
+            path.parent().unwrap_or(Path::new("")),
+            Some(base.to_string_lossy().to_string()),
+        )
+    };
+
+    let rotated = rotated_name(base.as_deref());
+    let names = list_dir(dir)?;
+    // the base names of the sets in a directory, only files that were rotated
+    // start a set
+    let bases: Vec<String> = names
+        .iter()
+        .filter_map(|name| rotated.captures(name))
+        .filter_map(|captures| captures.name("base"))
+        .map(|base| base.as_str().to_string())
+        .collect();
+
+    let mut sets: Vec<(String, Vec<String>)> = Vec::new();
+    for name in names {
+        let set_base = match (&base, rotated.captures(&name)) {
+            (Some(base), Some(_)) => base.clone(),
+            (None, Some(captures)) => captures["base"].to_string(),
+            (None, None) if bases.contains(&name) => name.clone(),
+            (_, None) => continue,
+        };
+
+        let file = dir.join(&name).to_string_lossy().to_string();
+        match sets.iter_mut().find(|(b, _)| *b == set_base) {
+            Some((_, files)) => files.push(file),
+            None => sets.push((set_base, vec![file])),
+        }
+    }
+
+    if sets.is_empty() {
+        return Err(LogError {
+            cause: format!("Unable to find any rotated logs for {}", path.display()),
+        });
+    }
+
+    Ok(sets.into_iter().map(|(_, files)| files).collect())
+}
+
+fn rotation_number(rotated: &Regex, path: &str) -> usize {
+    rotated
+        .captures(path)
+        .and_then(|captures| captures.name("number"))
+        .and_then(|number| number.as_str().parse().ok())
+        .unwrap_or(0)
+}
+
+/// Checks if two messages are the same ignoring where they where read from
+fn same_message(a: &Log, b: &Log) -> bool {
+    a.time == b.time
+        && a.pid == b.pid
+        && a.tid == b.tid
+        && a.logger == b.logger
+        && a.component == b.component
+        && a.file == b.file
+        && a.line == b.line
+        && a.level == b.level
+        && a.message == b.message
+}
+
+/// Reads the files of a rotated log set as one continuous stream.
+///
+/// The files are read in the order of their first message rather than by name,
+/// any messages at the start of a file that were already read from the end of
+/// the file before it are dropped.
+pub(crate) struct RotatedLogs {
+    files: VecDeque<Rotation>,
+    current: Option<LogStream>,
+    /// messages from the end of the current file that may be repeated in the next
+    tail: Vec<Log>,
+    /// messages from the end of the previous file that may be repeated in the current
+    overlap: Vec<Log>,
+    /// the time of the last message read from the current file
+    current_last: Option<NaiveDateTime>,
+    /// the time of the last message read from the previous file
+    previous_last: Option<NaiveDateTime>,
+}
+
+impl RotatedLogs {
+    pub fn new(paths: Vec<String>) -> Result<Self, LogError> {
+        let mut files = Vec::new();
+        for path in paths {
+            if let Some(first) = input::open(&path)?.next() {
+                files.push(Rotation {
+                    first: first?.time,
+                    path,
+                });
+            }
+        }
+        let rotated = rotated_name(None);
+        files.sort_by_cached_key(|file| {
+            (file.first, Reverse(rotation_number(&rotated, &file.path)))
+        });
+
+        Ok(RotatedLogs {
+            files: files.into(),
+            current: None,
+            tail: Vec::new(),
+            overlap: Vec::new(),
+            current_last: None,
+            previous_last: None,
+        })
+    }
+
+    fn is_duplicate(&mut self, log: &Log) -> bool {
+        match self.previous_last {
+            Some(last) if log.time <= last => self.overlap.iter().any(|old| same_message(old, log)),
+            Some(_) => {
+                self.overlap.clear();
+                self.previous_last = None;
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+impl Iterator for RotatedLogs {
+    type Item = Result<Log, LogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let current = match self.current.as_mut() {
+                Some(current) => current,
+                None => {
+                    let file = self.files.pop_front()?;
+                    match input::open(&file.path) {
+                        Ok(stream) => self.current.insert(stream),
+                        Err(err) => return Some(Err(err)),
+                    }
+                }
+            };
+
+            let log = match current.next() {
+                Some(Ok(log)) => log,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    self.current = None;
+                    self.overlap = std::mem::take(&mut self.tail);
+                    self.previous_last = self.current_last.take();
+                    continue;
+                }
+            };
+
+            if self.is_duplicate(&log) {
+                continue;
+            }
+
+            if let Some(next) = self.files.front() {
+                if log.time >= next.first {
+                    self.tail.push(log.clone());
+                }
+            }
+            self.current_last = Some(log.time);
+
+            return Some(Ok(log));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::{record, TempDir};
+
+    #[test]
+    fn rotated_logs_order() {
+        let dir = TempDir::new("rotation");
+
+        let files = vec![
+            ("app.log", vec![(5, "e"), (6, "f")]),
+            ("app.log.1", vec![(3, "c"), (4, "d"), (5, "e")]),
+            ("app.log.2", vec![(1, "a"), (2, "b"), (3, "c")]),
+            ("other.log", vec![(1, "z")]),
+        ];
+        for (name, records) in &files {
+            let text: String = records.iter().map(|(s, m)| record(*s).message(m)).collect();
+            fs::write(dir.join(name), text).unwrap();
+        }
+
+        let base = dir.join("app.log").to_string_lossy().to_string();
+        let sets = discover(&base).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].len(), 3);
+
+        let messages: Vec<String> = RotatedLogs::new(sets[0].clone())
+            .unwrap()
+            .map(|log| log.unwrap().message)
+            .collect();
+        assert_eq!(messages, vec!["a", "b", "c", "d", "e", "f"]);
+
+        let sets = discover(&dir.to_string_lossy()).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(sets[0].len(), 3);
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+};
+
+use chrono::NaiveDateTime;
+
+use crate::{index::Window, input, reader::RECORD_MARKER, Log, LogError};
+
+/// Messages can be written slightly out of order (e.g. by different threads) so
+/// the part of the file read is widened by this many seconds either side
+const SLACK_SECONDS: i64 = 2;
+/// How much of the file is read at a time when looking for the next message
+const PROBE_SIZE: usize = 16 * 1024;
+/// Give up looking for the next message after this much of the file
+const PROBE_LIMIT: usize = 4 * 1024 * 1024;
+/// How many places spread across the file are checked to make sure it is in time order
+const SAMPLES: usize = 32;
+
+fn error(path: &str, err: impl std::fmt::Display) -> LogError {
+    LogError {
+        cause: format!("Unable to read {}: {}", path, err),
+    }
+}
+
+/// A file being searched along with the times found so far at each offset
+struct Search {
+    path: String,
+    file: fs::File,
+    len: usize,
+    probes: Vec<(usize, i64)>,
+    /// set if a message could not be read, so the file can not be searched
+    damaged: bool,
+}
+
+impl Search {
+    /// Finds the first message that starts at or after an offset returning
+    /// where it starts and its time in seconds
+    fn probe(&mut self, offset: usize) -> Result<Option<(usize, i64)>, LogError> {
+        let mut data = Vec::new();
+        let mut start = None;
+
+        while data.len() < PROBE_LIMIT && offset + data.len() < self.len {
+            let read = data.len();
+            self.file
+                .seek(SeekFrom::Start((offset + read) as u64))
+                .map_err(|err| error(&self.path, err))?;
+            data.resize(read + PROBE_SIZE, 0);
+            let size = self
+                .file
+                .read(&mut data[read..])
+                .map_err(|err| error(&self.path, err))?;
+            data.truncate(read + size);
+            if size == 0 {
+                break;
+            }
+
+            if start.is_none() {
+                start = find_record(&data, offset);
+            }
+
+            // a message is complete once the end of its first line has been read
+            let Some(record) = start else { continue };
+            let Some(end) = data[record..].iter().position(|byte| *byte == b'\n') else {
+                continue;
+            };
+
+            let line = String::from_utf8_lossy(&data[record..record + end]).to_string();
+            let Ok(log) = Log::from(line) else {
+                self.damaged = true;
+                return Ok(None);
+            };
+            let time = log.time.and_utc().timestamp();
+            self.probes.push((offset + record, time));
+            return Ok(Some((offset + record, time)));
+        }
+
+        Ok(None)
+    }
+
+    /// Finds the offset of the first message at or after which every message
+    /// probed has a time after the target
+    fn search(&mut self, target: i64, inclusive: bool) -> Result<usize, LogError> {
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match self.probe(middle)? {
+                Some((record, time)) if time < target || (!inclusive && time == target) => {
+                    low = record + 1
+                }
+                _ => high = middle,
+            }
+        }
+
+        Ok(self
+            .probe(low)?
+            .map(|(record, _)| record)
+            .unwrap_or(self.len))
+    }
+
+    /// Checks that the times probed are in order, if not the file can not be
+    /// searched and needs to be read in full
+    fn in_order(&mut self) -> bool {
+        self.probes.sort();
+        self.probes.windows(2).all(|pair| pair[0].1 <= pair[1].1)
+    }
+
+    /// Checks the first line of every message between two offsets to make sure
+    /// none of them should have been kept, returning the number of lines read
+    /// or None if one is kept (or can't be read) and so the file can not be
+    /// skipped over
+    fn skippable(
+        &mut self,
+        from: usize,
+        to: usize,
+        kept: impl Fn(i64) -> bool,
+    ) -> Result<Option<usize>, LogError> {
+        self.file
+            .seek(SeekFrom::Start(from as u64))
+            .map_err(|err| error(&self.path, err))?;
+        let mut reader = BufReader::new(&self.file).take((to - from) as u64);
+
+        let (mut lines, mut line) = (0, Vec::new());
+        loop {
+            line.clear();
+            if reader
+                .read_until(b'\n', &mut line)
+                .map_err(|err| error(&self.path, err))?
+                == 0
+            {
+                return Ok(Some(lines));
+            }
+            lines += 1;
+
+            if line.starts_with(RECORD_MARKER.as_bytes()) {
+                let text = String::from_utf8_lossy(&line).trim_end().to_string();
+                match Log::from(text) {
+                    Ok(log) if !kept(log.time.and_utc().timestamp()) => {}
+                    _ => return Ok(None),
+                }
+            }
+        }
+    }
+}
+
+/// Finds where the first message in some data read from an offset starts
+fn find_record(data: &[u8], offset: usize) -> Option<usize> {
+    if offset == 0 && data.starts_with(RECORD_MARKER.as_bytes()) {
+        return Some(0);
+    }
+
+    let marker = format!("\n{}", RECORD_MARKER);
+    data.windows(marker.len())
+        .position(|window| window == marker.as_bytes())
+        .map(|index| index + 1)
+}
+
+/// Finds the part of a log that contains the messages in a time range by
+/// binary searching the file for them, as logs are written in time order.
+///
+/// None is returned if the file can not be searched, e.g. it is compressed, or
+/// the times found are out of order, in which case the whole file must be read.
+/// As a message out of order between the places probed could be missed, the
+/// first line of every message outside the window is checked to make sure it
+/// would not be kept, which is much quicker than reading them in full.
+pub(crate) fn window(
+    path: &str,
+    after: Option<NaiveDateTime>,
+    before: Option<NaiveDateTime>,
+) -> Result<Option<Window>, LogError> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(None);
+    };
+    if !metadata.is_file() || (after.is_none() && before.is_none()) {
+        return Ok(None);
+    }
+
+    let mut file = fs::File::open(path).map_err(|err| error(path, err))?;
+    let mut header = [0; 8];
+    let size = file.read(&mut header).map_err(|err| error(path, err))?;
+    if input::is_compressed(&header[..size]) {
+        return Ok(None);
+    }
+
+    let mut search = Search {
+        path: path.to_string(),
+        file,
+        len: metadata.len() as usize,
+        probes: Vec::new(),
+        damaged: false,
+    };
+
+    for sample in 1..SAMPLES {
+        search.probe(search.len * sample / SAMPLES)?;
+    }
+
+    // times are compared to the second in the same way as the --after and --before filters
+    let after = after.map(|time| time.and_utc().timestamp());
+    let before = before.map(|time| time.and_utc().timestamp());
+    let start = match after {
+        Some(after) => search.search(after - SLACK_SECONDS, true)?,
+        None => 0,
+    };
+    let end = match before {
+        Some(before) => search.search(before + SLACK_SECONDS, false)?,
+        None => search.len,
+    };
+
+    if search.damaged || !search.in_order() {
+        return Ok(None);
+    }
+
+    let kept = |time: i64| {
+        after.is_none_or(|after| time >= after) && before.is_none_or(|before| time <= before)
+    };
+    let Some(line) = search.skippable(0, start, kept)? else {
+        return Ok(None);
+    };
+    if search.skippable(end, search.len, kept)?.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(Window {
+        offset: start,
+        line: Some(line),
+        end: (end < search.len).then_some(end),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index;
+    use crate::test_support::{record, TempDir};
+
+    fn time(minute: usize) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(
+            &format!("Tue Jul  9 09:{:02}:00 2024 000000us", minute),
+            "%a %b %e %H:%M:%S %Y %fus",
+        )
+        .unwrap()
+    }
+
+    fn write(dir: &TempDir, name: &str, minutes: &[usize]) -> String {
+        let text: String = minutes
+            .iter()
+            .map(|minute| record(minute * 60).message(&minute.to_string()) + "  continued\n")
+            .collect();
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn seek_window() {
+        let dir = TempDir::new("seek");
+        let minutes: Vec<usize> = (0..60).collect();
+        let path = write(&dir, "ordered", &minutes);
+
+        let messages = |after: Option<usize>, before: Option<usize>| -> Vec<String> {
+            let window = window(&path, after.map(time), before.map(time))
+                .unwrap()
+                .unwrap();
+            index::open(&path, window)
+                .unwrap()
+                .map(|log| {
+                    let log = log.unwrap();
+                    format!(
+                        "{}@{}",
+                        log.message.lines().next().unwrap(),
+                        log.source.line_name()
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(messages(Some(57), None), vec!["57@115", "58@117", "59@119"]);
+        assert_eq!(messages(None, Some(1)), vec!["0@1", "1@3"]);
+        assert_eq!(messages(Some(30), Some(31)), vec!["30@61", "31@63"]);
+        assert_eq!(messages(Some(59), None), vec!["59@119"]);
+
+        // times that go backwards (e.g. two logs joined together) mean the file has to be read in full
+        let minutes: Vec<usize> = (30..60).chain(0..30).collect();
+        let path = write(&dir, "unordered", &minutes);
+        assert_eq!(window(&path, Some(time(30)), None).unwrap(), None);
+
+        // even if only one message outside the window is out of order
+        let mut minutes: Vec<usize> = (0..60).collect();
+        minutes[3] = 45;
+        minutes[50] = 1;
+        let path = write(&dir, "one-unordered", &minutes);
+        assert_eq!(window(&path, Some(time(45)), None).unwrap(), None);
+        assert_eq!(window(&path, None, Some(time(2))).unwrap(), None);
+        assert!(window(&path, Some(time(55)), None).unwrap().is_some());
+
+        // or a message that can't be read is found while searching
+        let minutes: Vec<usize> = (0..60).collect();
+        let path = write(&dir, "damaged", &minutes);
+        let text = fs::read_to_string(&path).unwrap();
+        let middle = text.len() / 2;
+        let broken = text[middle..].find("Tid#").unwrap() + middle;
+        fs::write(&path, format!("{}{}", &text[..broken], &text[broken + 4..])).unwrap();
+        assert_eq!(window(&path, Some(time(50)), None).unwrap(), None);
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::Log;
+
+/// Counts how many times each value of a field is seen
+#[derive(Debug, Default)]
+pub(crate) struct Counter {
+    counts: HashMap<String, usize>,
+}
+
+impl Counter {
+    pub fn add(&mut self, value: String) {
+        *self.counts.entry(value).or_default() += 1;
+    }
+
+    /// The most common values along with how many times they were seen, most
+    /// common first
+    pub fn top(&self, limit: usize) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = self
+            .counts
+            .iter()
+            .map(|(value, count)| (value.as_str(), *count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts.truncate(limit);
+        counts
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+}
+
+/// Formats a length of time in the largest units that make sense e.g. `1h 02m 03.500s`
+pub(crate) fn format_span(span: TimeDelta) -> String {
+    let micros = span.num_microseconds().unwrap_or(i64::MAX);
+    let seconds = micros as f64 / 1_000_000.0;
+    let minutes = micros / 60_000_000;
+    let hours = minutes / 60;
+    let days = hours / 24;
+    let seconds = seconds - (minutes * 60) as f64;
+
+    if days > 0 {
+        format!(
+            "{}d {:02}h {:02}m {:06.3}s",
+            days,
+            hours % 24,
+            minutes % 60,
+            seconds
+        )
+    } else if hours > 0 {
+        format!("{}h {:02}m {:06.3}s", hours, minutes % 60, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:06.3}s", minutes, seconds)
+    } else {
+        format!("{:.3}s", seconds)
+    }
+}
+
+/// Parses a length of time given as a number and a unit e.g. `500ms`, `5s`, `1m`, `2h` or `1d`
+pub(crate) fn parse_duration(text: &str) -> Result<TimeDelta, String> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("{} is not a length of time e.g. 5s", text))?;
+
+    let duration = match unit {
+        "ms" => TimeDelta::try_milliseconds(number),
+        "s" | "" => TimeDelta::try_seconds(number),
+        "m" => TimeDelta::try_minutes(number),
+        "h" => TimeDelta::try_hours(number),
+        "d" => TimeDelta::try_days(number),
+        _ => {
+            return Err(format!(
+                "unknown unit {} in {}, use one of ms, s, m, h or d",
+                unit, text
+            ))
+        }
+    };
+    duration.ok_or_else(|| format!("{} is too long a length of time", text))
+}
+
+/// Summary statistics of a set of log messages
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    total: usize,
+    first: Option<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+    levels: Counter,
+    components: Counter,
+    loggers: Counter,
+    pids: Counter,
+    tids: Counter,
+    sources: Counter,
+    messages: Counter,
+}
+
+impl Stats {
+    pub fn add(&mut self, log: &Log) {
+        self.total += 1;
+        self.first = Some(self.first.map_or(log.time, |first| first.min(log.time)));
+        self.last = Some(self.last.map_or(log.time, |last| last.max(log.time)));
+        self.levels.add(log.level.to_string());
+        self.components.add(log.component.clone());
+        self.loggers.add(log.logger.clone());
+        self.pids.add(log.pid.to_string());
+        self.tids.add(log.tid.to_string());
+        self.sources.add(format!("{}:{}", log.file, log.line));
+        self.messages.add(log.message.clone());
+    }
+
+    /// The time between the first and last message
+    pub fn span(&self) -> TimeDelta {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last - first,
+            _ => TimeDelta::zero(),
+        }
+    }
+
+    /// The average number of messages per second
+    pub fn rate(&self) -> f64 {
+        let seconds = self.span().num_microseconds().unwrap_or(i64::MAX) as f64 / 1_000_000.0;
+        if seconds > 0.0 {
+            self.total as f64 / seconds
+        } else {
+            self.total as f64
+        }
+    }
+
+    fn print_counter(title: &str, counter: &Counter, top: usize) {
+        println!();
+        if counter.len() > top {
+            println!("{} (top {} of {})", title, top, counter.len());
+        } else {
+            println!("{}", title);
+        }
+        for (value, count) in counter.top(top) {
+            println!("{:>10}  {}", count, value);
+        }
+    }
+
+    /// Prints the statistics with the `top` most common values of each field
+    pub fn print(&self, top: usize, date_fmt: &str) {
+        let time = |time: Option<NaiveDateTime>| {
+            time.map(|time| time.format(date_fmt).to_string())
+                .unwrap_or_default()
+        };
+
+        println!("Messages: {}", self.total);
+        println!("First:    {}", time(self.first));
+        println!("Last:     {}", time(self.last));
+        println!("Span:     {}", format_span(self.span()));
+        println!("Rate:     {:.3} messages/s", self.rate());
+
+        Stats::print_counter("Level", &self.levels, top);
+        Stats::print_counter("Component", &self.components, top);
+        Stats::print_counter("Logger", &self.loggers, top);
+        Stats::print_counter("Pid", &self.pids, top);
+        Stats::print_counter("Tid", &self.tids, top);
+        Stats::print_counter("Source", &self.sources, top);
+        Stats::print_counter("Message", &self.messages, top);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_support::logs;
+
+    #[test]
+    fn stats_counts() {
+        let text = "\
+Pid# 1 Tim# Tue Jul  9 09:09:27 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# a
+Pid# 1 Tim# Tue Jul  9 09:09:28 2024 000000us Tid# 2 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# a
+Pid# 2 Tim# Tue Jul  9 09:09:29 2024 000000us Tid# 1 Log# default Src# client Fil# vorb.C Lin# 2 Lvl# ERROR Msg# b
+Pid# 1 Tim# Tue Jul  9 09:09:31 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# INFO Msg# a
+";
+        let mut stats = Stats::default();
+        for log in logs(text) {
+            stats.add(&log);
+        }
+
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.span(), TimeDelta::seconds(4));
+        assert_eq!(stats.rate(), 1.0);
+        assert_eq!(stats.levels.top(10), vec![("INFO", 3), ("ERROR", 1)]);
+        assert_eq!(stats.pids.top(1), vec![("1", 3)]);
+        assert_eq!(
+            stats.sources.top(10),
+            vec![("vorb.C:1", 3), ("vorb.C:2", 1)]
+        );
+        assert_eq!(stats.messages.top(10), vec![("a", 3), ("b", 1)]);
+    }
+
+    #[test]
+    fn format_span_units() {
+        let cases = vec![
+            (TimeDelta::milliseconds(1500), "1.500s"),
+            (TimeDelta::seconds(62), "1m 02.000s"),
+            (TimeDelta::seconds(3723), "1h 02m 03.000s"),
+            (TimeDelta::seconds(90000), "1d 01h 00m 00.000s"),
+        ];
+
+        for (input, output) in cases {
+            assert_eq!(
+                format_span(input),
+                output,
+                "Checking {:?} becomes {}",
+                input,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        let cases = vec![
+            ("250ms", Ok(TimeDelta::milliseconds(250))),
+            ("5s", Ok(TimeDelta::seconds(5))),
+            ("5", Ok(TimeDelta::seconds(5))),
+            ("10m", Ok(TimeDelta::minutes(10))),
+            ("2h", Ok(TimeDelta::hours(2))),
+            ("1d", Ok(TimeDelta::days(1))),
+        ];
+
+        for (input, output) in cases {
+            assert_eq!(
+                parse_duration(input),
+                output,
+                "Checking {} is parsed",
+                input
+            );
+        }
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("999999999999999d").is_err());
+    }
+}
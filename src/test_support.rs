@@ -0,0 +1,91 @@
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use crate::{reader::LogReader, Log};
+
+/// A directory for the files a test reads, removed when the test ends even if
+/// it fails
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory named after the test and the process
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("vislog-{}-{}", name, std::process::id()));
+        // left over if a previous run was killed
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Builds the text of a message in the visibroker log format
+pub(crate) struct Record {
+    pid: usize,
+    second: usize,
+    level: String,
+}
+
+/// A message logged a number of seconds after 09:00 on Tue Jul 9 2024
+pub(crate) fn record(second: usize) -> Record {
+    Record {
+        pid: 1,
+        second,
+        level: String::from("INFO"),
+    }
+}
+
+impl Record {
+    pub fn pid(self, pid: usize) -> Self {
+        Record { pid, ..self }
+    }
+
+    pub fn level(self, level: &str) -> Self {
+        Record {
+            level: level.to_string(),
+            ..self
+        }
+    }
+
+    /// The line the message is logged as, ending in a newline
+    pub fn message(&self, message: &str) -> String {
+        format!(
+            "Pid# {} Tim# Tue Jul  9 {:02}:{:02}:{:02} 2024 000000us Tid# 1 Log# default Src# server Fil# vorb.C Lin# 1 Lvl# {} Msg# {}\n",
+            self.pid,
+            9 + self.second / 3600,
+            self.second / 60 % 60,
+            self.second % 60,
+            self.level,
+            message
+        )
+    }
+}
+
+/// Reads the messages in some text as if it was a file called test.log
+pub(crate) fn logs(text: &str) -> Vec<Log> {
+    LogReader::new(text.as_bytes(), "test.log")
+        .map(|log| log.unwrap())
+        .collect()
+}